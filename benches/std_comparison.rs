@@ -0,0 +1,72 @@
+//! # MyCell, MyRefCell and MyRc against their std counterparts
+//! Run with `cargo bench`, criterion writes the reports to target/criterion.
+
+use std::{
+    cell::{Cell, RefCell},
+    hint::black_box,
+    rc::Rc,
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use Pointers_Study_With_Core_Concepts::{cell::MyCell, rc::MyRc, refcell::MyRefCell};
+
+fn cell(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cell get/set");
+
+    let my_cell = MyCell::new(0u64);
+    group.bench_function("MyCell", |b| {
+        b.iter(|| {
+            let cell = black_box(&my_cell);
+            cell.set(cell.get() + 1);
+        })
+    });
+
+    let std_cell = Cell::new(0u64);
+    group.bench_function("Cell", |b| {
+        b.iter(|| {
+            let cell = black_box(&std_cell);
+            cell.set(cell.get() + 1);
+        })
+    });
+
+    group.finish();
+}
+
+fn refcell(c: &mut Criterion) {
+    let mut group = c.benchmark_group("refcell borrow/borrow_mut");
+
+    let my_refcell = MyRefCell::new(0u64);
+    group.bench_function("MyRefCell", |b| {
+        b.iter(|| {
+            let cell = black_box(&my_refcell);
+            let value = *cell.borrow();
+            *cell.borrow_mut() = value + 1;
+        })
+    });
+
+    let std_refcell = RefCell::new(0u64);
+    group.bench_function("RefCell", |b| {
+        b.iter(|| {
+            let cell = black_box(&std_refcell);
+            let value = *cell.borrow();
+            *cell.borrow_mut() = value + 1;
+        })
+    });
+
+    group.finish();
+}
+
+fn rc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rc clone/drop");
+
+    let my_rc = MyRc::new(0u64);
+    group.bench_function("MyRc", |b| b.iter(|| drop(black_box(my_rc.clone()))));
+
+    let std_rc = Rc::new(0u64);
+    group.bench_function("Rc", |b| b.iter(|| drop(black_box(std_rc.clone()))));
+
+    group.finish();
+}
+
+criterion_group!(benches, cell, refcell, rc);
+criterion_main!(benches);
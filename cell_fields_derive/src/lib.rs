@@ -0,0 +1,118 @@
+//! # derive(CellFields)
+//! Per field get/set/replace on a MyCell holding a struct,
+//! same as the tuple accessors MyCell has for tuples.
+//!
+//! Re-exported from the main crate with the derive feature,
+//! use it from there instead of depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields};
+
+/// # Info
+/// For `struct Player { hp: u32, name: String }` generates a trait
+/// `PlayerCellFields` implemented for `MyCell<Player>`, with
+/// get_hp/set_hp/replace_hp and get_name/set_name/replace_name.
+///
+/// # Required to go through a trait
+/// MyCell is foreign to the crate the struct lives in,
+/// so an inherent impl on MyCell<Player> isn't allowed there.
+/// The trait has the same visibility as the struct.
+///
+/// # Copy fields
+/// A derive can't tell which field types are Copy, so every field gets a get,
+/// bounded on the field being Copy. For a field like String the bound can
+/// never hold, so its get just can't be called. set and replace work for any field.
+///
+/// The generated code names MyCell by its full path, so the main crate
+/// has to be a dependency under its own name, not renamed.
+#[proc_macro_derive(CellFields)]
+pub fn derive_cell_fields(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "CellFields needs named fields, MyCell has get_0/set_0 for tuples",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "CellFields can only be derived for structs",
+            ))
+        }
+    };
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let trait_name = format_ident!("{}CellFields", name);
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut signatures = Vec::new();
+    let mut bodies = Vec::new();
+    for field in fields {
+        let field_name = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let get = format_ident!("get_{}", field_name);
+        let set = format_ident!("set_{}", field_name);
+        let replace = format_ident!("replace_{}", field_name);
+
+        // the for<'a> makes the bound not "trivial", so a field type that
+        // isn't Copy gives an uncallable get instead of a compile error
+        let get_sig =
+            quote!(fn #get(&self) -> #ty where for<'cell_fields> #ty: ::core::marker::Copy);
+        let set_sig = quote!(fn #set(&self, value: #ty));
+        let replace_sig = quote!(fn #replace(&self, value: #ty) -> #ty);
+
+        signatures.push(quote! {
+            #get_sig;
+            #set_sig;
+            #replace_sig;
+        });
+        bodies.push(quote! {
+            #get_sig {
+                // SAFE because MyCell never gives out refs to its value
+                unsafe { (*::Pointers_Study_With_Core_Concepts::cell::MyCell::as_ptr(self)).#field_name }
+            }
+
+            #set_sig {
+                ::core::mem::drop(self.#replace(value));
+            }
+
+            #replace_sig {
+                // SAFE because MyCell never gives out refs to its value,
+                // and nothing else runs while the &mut to the field is alive
+                unsafe {
+                    ::core::mem::replace(
+                        &mut (*::Pointers_Study_With_Core_Concepts::cell::MyCell::as_ptr(self)).#field_name,
+                        value,
+                    )
+                }
+            }
+        });
+    }
+
+    Ok(quote! {
+        #vis trait #trait_name #impl_generics #where_clause {
+            #(#signatures)*
+        }
+
+        impl #impl_generics #trait_name #ty_generics
+            for ::Pointers_Study_With_Core_Concepts::cell::MyCell<#name #ty_generics>
+        #where_clause
+        {
+            #(#bodies)*
+        }
+    })
+}
@@ -0,0 +1,798 @@
+//! Basic implementation of Arc thread safe shared pointer
+
+use crate::sync::{atomic, hint, AtomicPtr, AtomicUsize, Ordering};
+use alloc::{
+    alloc::{dealloc, Layout},
+    boxed::Box,
+};
+use core::{
+    marker::PhantomData,
+    mem::{self, ManuallyDrop},
+    ops::Deref,
+    ptr::{self, NonNull},
+};
+
+/// # Required to use atomics for the ref count
+/// Clones and drops can happen on different threads at the same time,
+/// so the count has to be updated with atomic read-modify-write operations.
+/// A MyCell like in MyRc would lose updates.
+///
+/// # Info
+/// Same idea as MyRc, multiple shared refs to the same value,
+/// deallocated when the last one goes away.
+///
+/// Never provides mutability.
+///
+/// # Orderings of the count
+/// Clones only bump the count with Relaxed, having a MyArc already
+/// keeps the value alive. Every drop decrements with Release, and
+/// only the one that brings the count to 0 does an Acquire fence before
+/// dropping the value, so all the other threads' uses of it happen before the drop.
+/// The same pairing is used for the weak count and the allocation.
+///
+/// # Common Usage
+/// Sharing read only data between threads,
+/// e.g config shared with worker threads.
+pub struct MyArc<T> {
+    inner: NonNull<ArcInner<T>>,
+    _marker: PhantomData<ArcInner<T>>,
+}
+
+/// # Required to impl Send and Sync by hand
+/// NonNull is neither Send nor Sync, so the compiler won't derive them.
+///
+/// Sending a MyArc to another thread shares the value with it,
+/// and the last arc may drop the value on any thread.
+/// So T has to be Sync (shared between threads) and Send (dropped on another one).
+///
+/// That's the difference from MyRc, which doesn't impl them at all:
+/// its count isn't atomic, while here it is. A MyArc<MyRc<i32>> is still
+/// neither Send nor Sync, because MyRc isn't.
+unsafe impl<T: Send + Sync> Send for MyArc<T> {}
+unsafe impl<T: Send + Sync> Sync for MyArc<T> {}
+
+/// # Struct to store T value and atomic ref counts of the Arc
+///
+/// # Required to wrap value T in ManuallyDrop
+/// The value is dropped when the last MyArc goes away,
+/// but the allocation stays alive until the last ArcWeak goes away too.
+pub struct ArcInner<T> {
+    ref_count: AtomicUsize,
+    // all MyArc together hold one weak ref, same as MyRc
+    weak_count: AtomicUsize,
+    value: ManuallyDrop<T>,
+}
+
+/// Way below usize::MAX, so even if many threads bump the count
+/// at the same time before one of them aborts, it can't wrap around.
+#[cfg(not(test))]
+const MAX_REFCOUNT: usize = isize::MAX as usize;
+
+/// Lowered in tests, so the overflow branch is reachable.
+#[cfg(test)]
+const MAX_REFCOUNT: usize = 1 << 10;
+
+impl<T> MyArc<T> {
+    pub fn new(value: T) -> Self {
+        let inner = Box::new(ArcInner {
+            ref_count: AtomicUsize::new(1),
+            weak_count: AtomicUsize::new(1),
+            value: ManuallyDrop::new(value),
+        });
+
+        MyArc {
+            // SAFE because box cannot give us a nullptr
+            inner: unsafe { NonNull::new_unchecked(Box::into_raw(inner)) },
+            _marker: PhantomData,
+        }
+    }
+
+    /// # Info
+    /// Creates an ArcWeak pointing to the same allocation.
+    pub fn downgrade(this: &MyArc<T>) -> ArcWeak<T> {
+        let old_weak = this.inner().weak_count.fetch_add(1, Ordering::Relaxed);
+        if old_weak > MAX_REFCOUNT {
+            crate::count_overflow();
+        }
+
+        ArcWeak { inner: this.inner }
+    }
+
+    /// # Info
+    /// Gives the value back if this is the only MyArc pointing to it,
+    /// otherwise gives the MyArc back in Err.
+    ///
+    /// # Required to use a CAS
+    /// Checking the count and then moving the value out would leave a window
+    /// where another thread clones its MyArc (or upgrades an ArcWeak).
+    /// The CAS from 1 to 0 claims the value in one step,
+    /// and once the count is 0 nobody can get a new strong ref anymore.
+    pub fn try_unwrap(this: MyArc<T>) -> Result<T, MyArc<T>> {
+        // Acquire pairs with the Release in the Drop of the other MyArc,
+        // so we see all their uses of the value before we take it
+        if this
+            .inner()
+            .ref_count
+            .compare_exchange(1, 0, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(this);
+        }
+
+        Ok(MyArc::take_value(this))
+    }
+
+    /// # Info
+    /// Gives the value back if this was the last MyArc, None otherwise.
+    ///
+    /// Unlike try_unwrap this always gives up our ref. When several threads
+    /// call it on their clones, exactly one of them gets the value,
+    /// while with try_unwrap they could all see a count above 1 and all fail.
+    pub fn into_inner(this: MyArc<T>) -> Option<T> {
+        // same Release/Acquire pairing as in Drop
+        if this.inner().ref_count.fetch_sub(1, Ordering::Release) != 1 {
+            mem::forget(this);
+            return None;
+        }
+        atomic::fence(Ordering::Acquire);
+
+        Some(MyArc::take_value(this))
+    }
+
+    /// # Info
+    /// Gives a mutable ref to the value, cloning it into a new allocation first
+    /// if other MyArc point to it. Threaded version of MyRc::make_mut.
+    ///
+    /// If only ArcWeak are left besides us, the value is moved
+    /// to a new allocation instead of cloned, and the ArcWeak can't upgrade anymore.
+    ///
+    /// # Required to take the strong count down to 0 first
+    /// Checking strong == 1 and then handing out the &mut would race with
+    /// an ArcWeak upgrading on another thread right after the check.
+    /// So we CAS the count from 1 to 0, from then on every upgrade fails,
+    /// and only after that we look at the weak count.
+    pub fn make_mut(this: &mut MyArc<T>) -> &mut T
+    where
+        T: Clone,
+    {
+        // Acquire pairs with the Release in the Drop of the other MyArc,
+        // so we see all their uses of the value before we mutate it
+        if this
+            .inner()
+            .ref_count
+            .compare_exchange(1, 0, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // other MyArc are still around, they keep the old value
+            *this = MyArc::new((**this).clone());
+        } else if this.inner().weak_count.load(Ordering::Relaxed) != 1 {
+            // only ArcWeak are left. Relaxed is enough, nobody can create new ones
+            // (that needs a MyArc or an ArcWeak we'd see), and a stale higher
+            // count only sends us down this slower path
+            let weak = ArcWeak { inner: this.inner };
+            // SAFE because the strong count is 0 on our behalf, so we own the value,
+            // and the old MyArc is overwritten without running its Drop
+            unsafe {
+                let value = ptr::read(MyArc::value_ptr(this));
+                ptr::write(this, MyArc::new(value));
+            }
+            // give back the weak ref held by the strong ones
+            drop(weak);
+        } else {
+            // we were the only ref of any kind, so nothing could
+            // have happened in between, just put our strong ref back
+            this.inner().ref_count.store(1, Ordering::Release);
+        }
+
+        // SAFE because now we are the only pointer to the allocation,
+        // and &mut this keeps it that way while the ref is alive
+        unsafe { &mut (*this.inner.as_ptr()).value }
+    }
+
+    /// Only to be called after the strong count went to 0 on our behalf.
+    fn take_value(this: MyArc<T>) -> T {
+        let this = ManuallyDrop::new(this);
+        // SAFE because the count is 0, so we own the value now,
+        // and this is never dropped so the value isn't dropped a second time
+        let value = unsafe { ptr::read(MyArc::value_ptr(&this)) };
+
+        // give back the weak ref held by the strong ones,
+        // frees the allocation if there are no ArcWeak left
+        drop(ArcWeak { inner: this.inner });
+        value
+    }
+
+    /// Same counts only view ArcWeak uses, so the only refs to the value
+    /// are the ones handed out by Deref/make_mut.
+    fn inner(&self) -> Counts<'_> {
+        // SAFE because inner struct is deallocated only when
+        // the last Arc goes away
+        unsafe { Counts::new(self.inner) }
+    }
+
+    /// Raw pointer to the value, projected without making a ref to the ArcInner.
+    fn value_ptr(this: &MyArc<T>) -> *const T {
+        unsafe { ptr::addr_of!((*this.inner.as_ptr()).value) as *const T }
+    }
+}
+
+impl<T> Clone for MyArc<T> {
+    fn clone(&self) -> Self {
+        // Relaxed is enough, we already have a ref so the value can't go away,
+        // and a new ref doesn't need to see any other memory
+        let old_refs = self.inner().ref_count.fetch_add(1, Ordering::Relaxed);
+        if old_refs > MAX_REFCOUNT {
+            crate::count_overflow();
+        }
+
+        MyArc {
+            inner: self.inner,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for MyArc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFE because the value lives as long as any MyArc does
+        unsafe { &*MyArc::value_ptr(self) }
+    }
+}
+
+impl<T> Drop for MyArc<T> {
+    fn drop(&mut self) {
+        // Release so everything this thread did with the value
+        // happens before the thread that frees it
+        if self.inner().ref_count.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+
+        // Acquire pairs with the Release above from the other threads,
+        // so we see all their uses of the value before we drop it
+        atomic::fence(Ordering::Acquire);
+
+        //SAFE because we were the last arc, so nobody can reach the value anymore
+        unsafe { ManuallyDrop::drop(&mut (*self.inner.as_ptr()).value) };
+
+        // give back the weak ref held by the strong ones,
+        // frees the allocation if there are no ArcWeak left
+        drop(ArcWeak { inner: self.inner });
+    }
+}
+
+/// # Info
+/// Weak version of MyArc, keeps only the allocation alive, not the value.
+///
+/// Has to be upgraded to a MyArc to get to the value,
+/// which fails once the last MyArc is gone, even if that happens on another thread.
+///
+/// # Common Usage
+/// Observers shared across threads, that shouldn't keep the subject alive.
+pub struct ArcWeak<T> {
+    inner: NonNull<ArcInner<T>>,
+}
+
+// Same reasoning as for MyArc, an upgrade gives a MyArc on this thread
+unsafe impl<T: Send + Sync> Send for ArcWeak<T> {}
+unsafe impl<T: Send + Sync> Sync for ArcWeak<T> {}
+
+impl<T> ArcWeak<T> {
+    /// # Info
+    /// Gives a MyArc if the value is still alive, None otherwise.
+    ///
+    /// Can't just fetch_add like clone does, the last MyArc might be dropped
+    /// on another thread right now. Incrementing from 0 would bring
+    /// a value that's being dropped back to life, so we only increment
+    /// with a CAS if the count we saw is still nonzero.
+    pub fn upgrade(&self) -> Option<MyArc<T>> {
+        let ref_count = self.inner().ref_count;
+        let mut n = ref_count.load(Ordering::Relaxed);
+        loop {
+            if n == 0 {
+                return None;
+            }
+
+            if n > MAX_REFCOUNT {
+                crate::count_overflow();
+            }
+
+            // Acquire so we see the value as the other arcs left it
+            match ref_count.compare_exchange_weak(n, n + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => {
+                    return Some(MyArc {
+                        inner: self.inner,
+                        _marker: PhantomData,
+                    })
+                }
+                Err(current) => n = current,
+            }
+        }
+    }
+
+    /// Only gives refs to the counts, the value might be in the middle
+    /// of being dropped (same as MyWeak).
+    fn inner(&self) -> Counts<'_> {
+        // SAFE because the allocation is freed only when
+        // the last weak ref goes away
+        unsafe { Counts::new(self.inner) }
+    }
+}
+
+/// # Info
+/// Refs to just the counts of an ArcInner, same as the Counts of MyRc.
+///
+/// A ref to the whole ArcInner would also cover the value, which might be
+/// mutably borrowed through make_mut or already dropped.
+struct Counts<'a> {
+    ref_count: &'a AtomicUsize,
+    weak_count: &'a AtomicUsize,
+}
+
+impl<'a> Counts<'a> {
+    /// SAFE only while the allocation is alive, for as long as 'a.
+    unsafe fn new<T>(inner: NonNull<ArcInner<T>>) -> Counts<'a> {
+        let inner = inner.as_ptr();
+        unsafe {
+            Counts {
+                ref_count: &(*inner).ref_count,
+                weak_count: &(*inner).weak_count,
+            }
+        }
+    }
+}
+
+impl<T> Clone for ArcWeak<T> {
+    fn clone(&self) -> Self {
+        let old_weak = self.inner().weak_count.fetch_add(1, Ordering::Relaxed);
+        if old_weak > MAX_REFCOUNT {
+            crate::count_overflow();
+        }
+
+        ArcWeak { inner: self.inner }
+    }
+}
+
+impl<T> Drop for ArcWeak<T> {
+    fn drop(&mut self) {
+        // same Release/Acquire pairing as in MyArc::drop
+        if self.inner().weak_count.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+
+        atomic::fence(Ordering::Acquire);
+
+        //SAFE because the allocation came from the Box in MyArc::new,
+        //which has the same layout. The value was already dropped by the last arc,
+        //freeing the memory directly doesn't make a ref to it or drop it again
+        unsafe { dealloc(self.inner.as_ptr().cast(), Layout::new::<ArcInner<T>>()) };
+    }
+}
+
+/// # Info
+/// Holds a MyArc that can be replaced atomically while other threads read it.
+///
+/// load gives a MyArc clone of the current value, store and swap put a new one in.
+/// Readers that loaded the old value keep it alive through their own MyArc,
+/// so a swap never pulls the value out from under them.
+///
+/// # Required to count readers in flight
+/// load has to read the pointer and then bump the strong count,
+/// and a swap on another thread could drop the last MyArc in between.
+/// So every load announces itself in readers first, and a swap waits
+/// until no load is halfway through before it lets the old MyArc go.
+///
+/// A steady stream of loads can keep a swap waiting, same as the writers of MyRwLock.
+///
+/// # Common Usage
+/// Read mostly config that gets reloaded now and then,
+/// readers never block each other and never see a half written config.
+pub struct MyArcSwap<T> {
+    ptr: AtomicPtr<ArcInner<T>>,
+    readers: AtomicUsize,
+    // owns one strong ref of the current value
+    _marker: PhantomData<MyArc<T>>,
+}
+
+// Same bounds as MyArc, the value is shared with and dropped on any thread
+unsafe impl<T: Send + Sync> Send for MyArcSwap<T> {}
+unsafe impl<T: Send + Sync> Sync for MyArcSwap<T> {}
+
+impl<T> MyArcSwap<T> {
+    pub fn new(value: MyArc<T>) -> Self {
+        MyArcSwap {
+            ptr: AtomicPtr::new(MyArcSwap::into_ptr(value)),
+            readers: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// # Info
+    /// Gives a new MyArc to the current value.
+    pub fn load(&self) -> MyArc<T> {
+        self.readers.fetch_add(1, Ordering::Relaxed);
+        // pairs with the fence in swap: either we read the new pointer,
+        // or the swap sees our announcement and waits for us.
+        // Acquire on the load so we see the value the pointer was published with
+        atomic::fence(Ordering::SeqCst);
+        let ptr = self.ptr.load(Ordering::Acquire);
+
+        // SAFE because the strong ref we hold for ptr isn't given up
+        // while a load is in flight, and ManuallyDrop keeps it ours
+        let current = ManuallyDrop::new(unsafe { MyArcSwap::from_ptr(ptr) });
+        let loaded = MyArc::clone(&current);
+
+        // Release so the swap waiting for us sees the count we just bumped
+        self.readers.fetch_sub(1, Ordering::Release);
+        loaded
+    }
+
+    /// # Info
+    /// Puts the new value in and drops our MyArc to the old one.
+    pub fn store(&self, value: MyArc<T>) {
+        drop(self.swap(value));
+    }
+
+    /// # Info
+    /// Puts the new value in and gives back the MyArc to the old one.
+    ///
+    /// Spins until no load that could have seen the old pointer is halfway through.
+    pub fn swap(&self, value: MyArc<T>) -> MyArc<T> {
+        // Release publishes the new value to the loads, Acquire gives us the old one
+        let old = self.ptr.swap(MyArcSwap::into_ptr(value), Ordering::AcqRel);
+
+        // without the fence we could read a count from before a load that
+        // already saw old announced itself (Acquire alone allows that)
+        atomic::fence(Ordering::SeqCst);
+
+        // Acquire pairs with the Release in load,
+        // every load that read old is done cloning it once this hits 0
+        while self.readers.load(Ordering::Acquire) != 0 {
+            hint::spin_loop();
+        }
+
+        // SAFE because the strong ref for old was held by us,
+        // and no load can reach old anymore
+        unsafe { MyArcSwap::from_ptr(old) }
+    }
+
+    /// Gives up the MyArc without touching the count, the pointer owns that strong ref now.
+    fn into_ptr(value: MyArc<T>) -> *mut ArcInner<T> {
+        ManuallyDrop::new(value).inner.as_ptr()
+    }
+
+    /// # Safety
+    /// ptr has to come from into_ptr, and the strong ref it owns moves into the MyArc.
+    unsafe fn from_ptr(ptr: *mut ArcInner<T>) -> MyArc<T> {
+        MyArc {
+            // SAFE because into_ptr only ever gives non null pointers
+            inner: unsafe { NonNull::new_unchecked(ptr) },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for MyArcSwap<T> {
+    fn drop(&mut self) {
+        // &mut self, so no load or swap can be running
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        // SAFE because we still own the strong ref of the current value
+        drop(unsafe { MyArcSwap::from_ptr(ptr) });
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        sync::Barrier,
+        thread, vec,
+        vec::Vec,
+    };
+
+    /// Counts its drops, to catch a value dropped twice or never.
+    struct DropCount<'a>(&'a AtomicUsize);
+
+    impl Drop for DropCount<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn strong_count_overflow_panics_before_wrapping() {
+        let drops = AtomicUsize::new(0);
+        let arc = MyArc::new(DropCount(&drops));
+        for _ in 0..MAX_REFCOUNT {
+            mem::forget(arc.clone());
+        }
+
+        let clone = panic::catch_unwind(AssertUnwindSafe(|| arc.clone()));
+        assert!(clone.is_err());
+        // the failed clone did bump the count, but nowhere near wrapping
+        let ref_count = &arc.inner().ref_count;
+        assert_eq!(ref_count.load(Ordering::Relaxed), MAX_REFCOUNT + 2);
+
+        // put back the count of just the one arc left, so it can free the value
+        ref_count.store(1, Ordering::Relaxed);
+        drop(arc);
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn weak_count_overflow_panics_before_wrapping() {
+        let arc = MyArc::new(5);
+        let weak = MyArc::downgrade(&arc);
+        for _ in 1..MAX_REFCOUNT {
+            mem::forget(weak.clone());
+        }
+
+        let downgrade = panic::catch_unwind(AssertUnwindSafe(|| MyArc::downgrade(&arc)));
+        assert!(downgrade.is_err());
+        let clone = panic::catch_unwind(AssertUnwindSafe(|| weak.clone()));
+        assert!(clone.is_err());
+        let weak_count = weak.inner().weak_count;
+        assert_eq!(weak_count.load(Ordering::Relaxed), MAX_REFCOUNT + 3);
+        assert_eq!(weak.upgrade().as_deref(), Some(&5));
+
+        // the arcs' implicit weak plus the one weak left
+        weak_count.store(2, Ordering::Relaxed);
+        drop(weak);
+        assert_eq!(MyArc::try_unwrap(arc).ok(), Some(5));
+    }
+
+    #[test]
+    fn moves_into_another_thread() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<MyArc<i32>>();
+
+        let arc = MyArc::new(5);
+        let clone = arc.clone();
+        let seen = thread::spawn(move || *clone).join().unwrap();
+        assert_eq!(seen, 5);
+        assert_eq!(MyArc::try_unwrap(arc).ok(), Some(5));
+    }
+
+    #[test]
+    fn stress_clone_drop_drops_once() {
+        const THREADS: usize = 8;
+        let (rounds, iters) = if cfg!(miri) { (2, 20) } else { (20, 1000) };
+
+        for _ in 0..rounds {
+            let drops = AtomicUsize::new(0);
+            let arc = MyArc::new(DropCount(&drops));
+            // the barrier starts every thread at once, so their clones
+            // and drops overlap as much as possible
+            let barrier = Barrier::new(THREADS + 1);
+            thread::scope(|s| {
+                for _ in 0..THREADS {
+                    let (arc, barrier) = (arc.clone(), &barrier);
+                    s.spawn(move || {
+                        barrier.wait();
+                        for _ in 0..iters {
+                            let clone = arc.clone();
+                            let weak = MyArc::downgrade(&clone);
+                            drop(clone);
+                            drop(weak.upgrade());
+                        }
+                    });
+                }
+                barrier.wait();
+                drop(arc);
+            });
+            assert_eq!(drops.load(Ordering::Relaxed), 1);
+        }
+    }
+
+    #[test]
+    fn last_drop_on_another_thread_drops_once() {
+        let drops = AtomicUsize::new(0);
+        let arc = MyArc::new(DropCount(&drops));
+        let weak = MyArc::downgrade(&arc);
+        thread::scope(|s| {
+            let clones: Vec<_> = (0..4).map(|_| arc.clone()).collect();
+            drop(arc);
+            for clone in clones {
+                s.spawn(move || drop(clone));
+            }
+        });
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn upgrade_races_the_last_drop() {
+        for _ in 0..if cfg!(miri) { 10 } else { 500 } {
+            let drops = AtomicUsize::new(0);
+            let arc = MyArc::new((DropCount(&drops), 7));
+            let weak = MyArc::downgrade(&arc);
+            thread::scope(|s| {
+                s.spawn(|| {
+                    // either the value is still there in one piece, or it's gone
+                    if let Some(arc) = weak.upgrade() {
+                        assert_eq!(arc.1, 7);
+                    }
+                });
+                drop(arc);
+            });
+            assert_eq!(drops.load(Ordering::Relaxed), 1);
+            assert!(weak.upgrade().is_none());
+        }
+    }
+
+    #[test]
+    fn into_inner_race_has_exactly_one_winner() {
+        const THREADS: usize = 4;
+        for _ in 0..if cfg!(miri) { 5 } else { 200 } {
+            let arc = MyArc::new(5);
+            // the original goes to a thread too, or dropping it here could be the last drop
+            let mut arcs: Vec<_> = (1..THREADS).map(|_| arc.clone()).collect();
+            arcs.push(arc);
+            let barrier = Barrier::new(THREADS);
+            let winners = AtomicUsize::new(0);
+            thread::scope(|s| {
+                for arc in arcs {
+                    let (barrier, winners) = (&barrier, &winners);
+                    s.spawn(move || {
+                        barrier.wait();
+                        // try_unwrap can fail everywhere, but gives the arc back
+                        let arc = match MyArc::try_unwrap(arc) {
+                            Ok(value) => {
+                                assert_eq!(value, 5);
+                                winners.fetch_add(1, Ordering::Relaxed);
+                                return;
+                            }
+                            Err(arc) => arc,
+                        };
+                        if let Some(value) = MyArc::into_inner(arc) {
+                            assert_eq!(value, 5);
+                            winners.fetch_add(1, Ordering::Relaxed);
+                        }
+                    });
+                }
+            });
+            assert_eq!(winners.load(Ordering::Relaxed), 1);
+        }
+    }
+
+    #[test]
+    fn make_mut_clones_while_readers_hold_the_value() {
+        let mut arc = MyArc::new(vec![1]);
+        let before = &*arc as *const Vec<i32>;
+        // unique, so the value is changed in place
+        MyArc::make_mut(&mut arc).push(2);
+        assert_eq!(&*arc as *const Vec<i32>, before);
+
+        let barrier = Barrier::new(3);
+        thread::scope(|s| {
+            for _ in 0..2 {
+                let (reader, barrier) = (arc.clone(), &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    // the writer got its own copy, ours is untouched
+                    barrier.wait();
+                    assert_eq!(*reader, [1, 2]);
+                });
+            }
+            barrier.wait();
+            MyArc::make_mut(&mut arc).push(3);
+            barrier.wait();
+        });
+        assert_ne!(&*arc as *const Vec<i32>, before);
+        assert_eq!(*arc, [1, 2, 3]);
+    }
+
+    #[test]
+    fn arc_swap_readers_never_see_a_freed_value() {
+        let swap = MyArcSwap::new(MyArc::new(vec![0; 1]));
+        let stores = if cfg!(miri) { 20 } else { 2000 };
+        let done = AtomicUsize::new(0);
+        thread::scope(|s| {
+            s.spawn(|| {
+                for n in 1..=stores {
+                    // every value is as long as its first element says + 1
+                    swap.store(MyArc::new(vec![n; n + 1]));
+                }
+                done.store(1, Ordering::Release);
+            });
+            for _ in 0..2 {
+                s.spawn(|| {
+                    while done.load(Ordering::Acquire) == 0 {
+                        let value = swap.load();
+                        assert_eq!(value.len(), value[0] + 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(swap.load().len(), stores + 1);
+    }
+}
+
+/// # Loom models
+/// Every interleaving of the threads below is run, with the atomics
+/// from src/sync.rs swapped for loom's, see README.
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::{
+        sync::{atomic::AtomicUsize, Arc},
+        thread,
+    };
+
+    struct DropCount(Arc<AtomicUsize>);
+
+    impl Drop for DropCount {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn clone_drop_drops_once() {
+        loom::model(|| {
+            let drops = Arc::new(AtomicUsize::new(0));
+            let arc = MyArc::new(DropCount(drops.clone()));
+            let clone = arc.clone();
+
+            let t = thread::spawn(move || drop(clone));
+            drop(arc);
+            t.join().unwrap();
+
+            assert_eq!(drops.load(Ordering::Relaxed), 1);
+        });
+    }
+
+    #[test]
+    fn upgrade_races_the_last_drop() {
+        loom::model(|| {
+            let drops = Arc::new(AtomicUsize::new(0));
+            let arc = MyArc::new(DropCount(drops.clone()));
+            let weak = MyArc::downgrade(&arc);
+
+            // either the upgrade wins and keeps the value alive for a bit,
+            // or it sees the strong count at 0 and gives None
+            let t = thread::spawn(move || drop(weak.upgrade()));
+            drop(arc);
+            t.join().unwrap();
+
+            assert_eq!(drops.load(Ordering::Relaxed), 1);
+        });
+    }
+
+    #[test]
+    fn make_mut_races_an_upgrade() {
+        loom::model(|| {
+            let mut arc = MyArc::new(1usize);
+            let weak = MyArc::downgrade(&arc);
+
+            let t = thread::spawn(move || weak.upgrade().map(|arc| *arc));
+            *MyArc::make_mut(&mut arc) += 1;
+            let seen = t.join().unwrap();
+
+            // the upgrade sees the value before or after make_mut, never a torn one
+            assert!(matches!(seen, None | Some(1) | Some(2)));
+            assert_eq!(*arc, 2);
+        });
+    }
+
+    #[test]
+    fn arc_swap_load_races_a_store() {
+        loom::model(|| {
+            let swap = MyArc::new(MyArcSwap::new(MyArc::new(1usize)));
+            let reader = swap.clone();
+
+            let t = thread::spawn(move || {
+                let value = reader.load();
+                assert!(*value == 1 || *value == 2);
+            });
+            swap.store(MyArc::new(2));
+            t.join().unwrap();
+
+            assert_eq!(*swap.load(), 2);
+        });
+    }
+}
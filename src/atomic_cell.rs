@@ -0,0 +1,273 @@
+//! # Basic implementation of an atomic Cell
+//! With most essential info about it
+
+use core::{
+    any::TypeId,
+    cell::UnsafeCell,
+    hint, mem,
+    sync::atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU8, Ordering},
+};
+
+// not every target has 64 bit atomics, e.g most 32 bit microcontrollers
+#[cfg(target_has_atomic = "64")]
+use core::sync::atomic::AtomicU64;
+
+/// # Info
+/// The Sync counterpart to MyCell: get by copy, set by move,
+/// never a ref to the value inside, but safe to share between threads.
+///
+/// If T is a primitive integer, float, bool or char, the value is read
+/// and written through an atomic integer of the same width,
+/// so no lock is ever taken. Anything else falls back to a small spin lock
+/// around the value, and so do 8 byte types on targets without 64 bit atomics.
+///
+/// # Required to align to 8
+/// The atomic integers need their natural alignment, which T might not have,
+/// e.g a struct of two u16 is 4 bytes but only aligned to 2.
+/// Aligning the whole cell to 8 lets every width use its atomic.
+///
+/// # Padding
+/// The atomic path turns T into an integer of the same size,
+/// so T must not have padding bytes, since those are uninit and reading them
+/// as part of an integer is UB. The size alone can't tell us that,
+/// e.g (u8, u16) is 4 bytes with one of them padding.
+///
+/// There is no way to ask the compiler about padding, so the atomic path
+/// only takes the primitives, which we know have none, and is picked by
+/// comparing TypeIds. That's why T has to be 'static.
+/// Structs always take the lock, even ones without padding.
+///
+/// # Common Usage
+/// Small Copy values shared between threads,
+/// e.g flags or counters that aren't plain integers.
+#[repr(C, align(8))]
+pub struct MySyncCell<T> {
+    value: UnsafeCell<T>,
+    // only used when T has no matching atomic
+    locked: AtomicBool,
+}
+
+/// # Required to impl Sync by hand
+/// UnsafeCell is not Sync, so the compiler won't derive it.
+///
+/// We never give out a ref to the value, T only ever gets copied in and out,
+/// so it has to be Send but doesn't need to be Sync.
+unsafe impl<T: Copy + Send> Sync for MySyncCell<T> {}
+
+/// Runs $atomic with $a bound to the atomic that matches the size of T,
+/// or $fallback when T is not a primitive or there is no atomic that big.
+macro_rules! with_atomic {
+    ($this:expr, $a:ident => $atomic:expr, $fallback:expr) => {{
+        let ptr = $this.value.get();
+        // 0 takes the lock, whatever the size
+        let width = if is_primitive::<T>() {
+            mem::size_of::<T>()
+        } else {
+            0
+        };
+        // SAFE because the cell is aligned to 8, T is exactly as big as the atomic
+        // and has no padding, and for a given T we always take the same branch,
+        // so the value is never accessed both atomically and not
+        unsafe {
+            match width {
+                1 => {
+                    let $a = &*(ptr as *const AtomicU8);
+                    $atomic
+                }
+                2 => {
+                    let $a = &*(ptr as *const AtomicU16);
+                    $atomic
+                }
+                4 => {
+                    let $a = &*(ptr as *const AtomicU32);
+                    $atomic
+                }
+                #[cfg(target_has_atomic = "64")]
+                8 => {
+                    let $a = &*(ptr as *const AtomicU64);
+                    $atomic
+                }
+                _ => $fallback,
+            }
+        }
+    }};
+}
+
+/// # Info
+/// True for the types we know have no padding,
+/// and where every integer we read back came from storing a valid T.
+fn is_primitive<T: 'static>() -> bool {
+    let id = TypeId::of::<T>();
+    [
+        TypeId::of::<u8>(),
+        TypeId::of::<u16>(),
+        TypeId::of::<u32>(),
+        TypeId::of::<u64>(),
+        TypeId::of::<usize>(),
+        TypeId::of::<i8>(),
+        TypeId::of::<i16>(),
+        TypeId::of::<i32>(),
+        TypeId::of::<i64>(),
+        TypeId::of::<isize>(),
+        TypeId::of::<f32>(),
+        TypeId::of::<f64>(),
+        TypeId::of::<bool>(),
+        TypeId::of::<char>(),
+    ]
+    .contains(&id)
+}
+
+/// Reinterprets the bytes of one Copy type as another of the same size.
+///
+/// SAFE only when A and B are the same size and A has no padding.
+unsafe fn transmute_bits<A: Copy, B: Copy>(value: A) -> B {
+    debug_assert_eq!(mem::size_of::<A>(), mem::size_of::<B>());
+    mem::transmute_copy(&value)
+}
+
+impl<T: Copy + 'static> MySyncCell<T> {
+    pub fn new(value: T) -> Self {
+        MySyncCell {
+            value: UnsafeCell::new(value),
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    /// # Info
+    /// Acquire, so we see everything the thread that stored the value did before.
+    pub fn load(&self) -> T {
+        with_atomic!(
+            self,
+            a => transmute_bits(a.load(Ordering::Acquire)),
+            self.with_lock(|ptr| *ptr)
+        )
+    }
+
+    /// # Info
+    /// Release, so whoever loads the value also sees everything we did before.
+    pub fn store(&self, value: T) {
+        with_atomic!(
+            self,
+            a => a.store(transmute_bits(value), Ordering::Release),
+            self.with_lock(|ptr| *ptr = value)
+        )
+    }
+
+    /// Stores the new value and gives back the old one in one step.
+    pub fn swap(&self, value: T) -> T {
+        with_atomic!(
+            self,
+            a => transmute_bits(a.swap(transmute_bits(value), Ordering::AcqRel)),
+            self.with_lock(|ptr| mem::replace(&mut *ptr, value))
+        )
+    }
+
+    /// Consumes the cell and gives back the value inside.
+    pub fn into_inner(self) -> T {
+        // no other threads to worry about, we own the cell
+        self.value.into_inner()
+    }
+
+    /// # Info
+    /// Spin lock for the types that have no matching atomic.
+    ///
+    /// SAFE to deref the pointer inside f, since we hold the lock until it returns.
+    unsafe fn with_lock<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            hint::spin_loop();
+        }
+
+        let result = f(self.value.get());
+        self.locked.store(false, Ordering::Release);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::{sync::Barrier, thread};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Pair {
+        a: u16,
+        b: u16,
+    }
+
+    #[test]
+    fn u32_load_store_swap() {
+        let cell = MySyncCell::new(1u32);
+        assert_eq!(cell.load(), 1);
+        cell.store(2);
+        assert_eq!(cell.swap(3), 2);
+        assert_eq!(cell.into_inner(), 3);
+    }
+
+    #[test]
+    fn padded_types_take_the_lock() {
+        assert!(!is_primitive::<(u8, u16)>());
+        assert!(!is_primitive::<Pair>());
+        assert!(is_primitive::<char>());
+
+        // would read the padding byte as part of a u32 on the atomic path
+        let cell = MySyncCell::new((1u8, 2u16));
+        assert_eq!(cell.load(), (1, 2));
+        assert_eq!(cell.swap((3, 4)), (1, 2));
+        assert_eq!(cell.load(), (3, 4));
+    }
+
+    #[test]
+    fn u32_is_consistent_across_threads() {
+        const THREADS: u32 = 4;
+        const STORES: u32 = 100;
+
+        let cell = MySyncCell::new(0u32);
+        let barrier = Barrier::new(THREADS as usize);
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let (cell, barrier) = (&cell, &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    for i in 0..STORES {
+                        // every value ever stored has the thread in the top bits
+                        // and a counter in the low ones
+                        cell.store(t << 16 | i);
+                        let seen = cell.load();
+                        assert!(seen >> 16 < THREADS && seen & 0xffff < STORES);
+                    }
+                });
+            }
+        });
+        assert_eq!(cell.load() & 0xffff, STORES - 1);
+    }
+
+    #[test]
+    fn struct_is_never_torn() {
+        const THREADS: u16 = 4;
+
+        let cell = MySyncCell::new(Pair { a: 0, b: 0 });
+        let barrier = Barrier::new(THREADS as usize);
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let (cell, barrier) = (&cell, &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    for i in 0..100 {
+                        let n = t * 100 + i;
+                        let old = cell.swap(Pair { a: n, b: n });
+                        // both halves always come from the same store
+                        assert_eq!(old.a, old.b);
+                        let seen = cell.load();
+                        assert_eq!(seen.a, seen.b);
+                    }
+                });
+            }
+        });
+    }
+}
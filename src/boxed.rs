@@ -0,0 +1,289 @@
+//! # Basic implementation of a Box owning pointer
+//! With most essential info about it
+
+use ::alloc::alloc::{self, Layout};
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+};
+
+#[cfg(feature = "nightly")]
+use core::{marker::Unsize, ops::CoerceUnsized};
+
+/// # Info
+/// Owns a single value on the heap, and frees it when dropped.
+///
+/// The simplest smart pointer there is: no counts, no borrow flags,
+/// just an allocation that lives exactly as long as the box.
+///
+/// # Required to allocate by hand
+/// Going through the alloc crate instead of Box shows what Box does under the hood:
+/// pick a layout, ask the allocator, write the value in, and undo it all on drop.
+///
+/// # Zero sized types
+/// A ZST takes no memory, so nothing is allocated for it.
+/// The pointer is just a dangling, well aligned one.
+///
+/// # Trait objects
+/// With the nightly feature, MyBox<T> coerces to MyBox<dyn Trait>
+/// the same way Box does, see the CoerceUnsized impl below.
+///
+/// # Common Usage
+/// Putting large values or recursive types on the heap
+/// e.g a linked list node that points to the next node.
+pub struct MyBox<T: ?Sized> {
+    ptr: NonNull<T>,
+    // tells the drop checker that we own a T
+    _marker: PhantomData<T>,
+}
+
+/// # Required to impl Send and Sync by hand
+/// NonNull is neither, but MyBox owns its T like a plain T would,
+/// so it's Send/Sync exactly when T is.
+unsafe impl<T: ?Sized + Send> Send for MyBox<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for MyBox<T> {}
+
+impl<T> MyBox<T> {
+    pub fn new(value: T) -> Self {
+        let layout = Layout::new::<T>();
+
+        let ptr = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            // SAFE because the layout is not zero sized
+            let raw = unsafe { alloc::alloc(layout) } as *mut T;
+            match NonNull::new(raw) {
+                Some(ptr) => ptr,
+                None => alloc::handle_alloc_error(layout),
+            }
+        };
+
+        // SAFE because ptr is valid and aligned for a T, either from the allocator
+        // or dangling for a ZST, where writes don't touch memory at all
+        unsafe { ptr.as_ptr().write(value) };
+
+        MyBox {
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> MyBox<T> {
+    /// # Info
+    /// Turns the box into a raw pointer without freeing it,
+    /// ownership of the value goes with the pointer.
+    ///
+    /// The value leaks unless the pointer is turned back with from_raw.
+    ///
+    /// # Common Usage
+    /// Handing an owned value over FFI, and taking it back later to drop it.
+    pub fn into_raw(b: MyBox<T>) -> *mut T {
+        let ptr = b.ptr.as_ptr();
+        // skip our Drop, the pointer owns the value now
+        mem::forget(b);
+        ptr
+    }
+
+    /// # Info
+    /// Takes back ownership of a pointer from into_raw,
+    /// so the value gets dropped and freed with the box.
+    ///
+    /// # Safety
+    /// ptr has to come from MyBox::into_raw, and each of those pointers
+    /// can only be turned back once, a second box would free it twice.
+    pub unsafe fn from_raw(ptr: *mut T) -> MyBox<T> {
+        MyBox {
+            // SAFE because pointers from into_raw are never null
+            ptr: unsafe { NonNull::new_unchecked(ptr) },
+            _marker: PhantomData,
+        }
+    }
+
+    /// # Info
+    /// Consumes the box without freeing it, and gives back a mutable ref
+    /// that lives as long as you want, up to 'static.
+    ///
+    /// The value is never dropped and the memory is never freed.
+    ///
+    /// # Common Usage
+    /// Data that should live for the rest of the program, e.g config
+    /// built at startup and then handed out as &'static.
+    pub fn leak<'a>(b: MyBox<T>) -> &'a mut T
+    where
+        T: 'a,
+    {
+        let ptr = b.ptr;
+        // skip our Drop, so nothing gets dropped or freed
+        mem::forget(b);
+        // SAFE because the allocation is never freed now,
+        // and we were the only owner, so the ref is unique
+        unsafe { &mut *ptr.as_ptr() }
+    }
+}
+
+/// # Info
+/// Lets the compiler turn MyBox<T> into MyBox<U> when T: Unsize<U>,
+/// e.g MyBox<String> into MyBox<dyn Display>, or MyBox<[i32; 3]> into MyBox<[i32]>.
+///
+/// The NonNull<T> inside gets turned into a fat pointer,
+/// which carries the vtable (or the length) next to the address.
+/// Box works the exact same way, this is all there is to Box<dyn Trait>.
+#[cfg(feature = "nightly")]
+impl<T: ?Sized + Unsize<U>, U: ?Sized> CoerceUnsized<MyBox<U>> for MyBox<T> {}
+
+impl<T: ?Sized> Deref for MyBox<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFE because the box owns a valid T for as long as it's alive
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MyBox<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFE because &mut self means nobody else is looking at the T
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for MyBox<T> {
+    fn drop(&mut self) {
+        // SAFE because we own the T and nobody will touch it after this.
+        // The layout is read before the value is dropped,
+        // and it's the same layout the memory was allocated with
+        unsafe {
+            let layout = Layout::for_value(self.ptr.as_ref());
+            ptr::drop_in_place(self.ptr.as_ptr());
+
+            // ZSTs never got an allocation
+            if layout.size() != 0 {
+                alloc::dealloc(self.ptr.as_ptr() as *mut u8, layout);
+            }
+        }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MyBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for MyBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::{
+        cell::Cell,
+        sync::atomic::{AtomicPtr, Ordering},
+    };
+    use std::{format, string::String, vec, vec::Vec};
+
+    struct DropCount<'a>(&'a Cell<usize>);
+
+    impl Drop for DropCount<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn value_drops_once() {
+        let drops = Cell::new(0);
+        let b = MyBox::new(DropCount(&drops));
+        assert_eq!(drops.get(), 0);
+        drop(b);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn zero_sized_values() {
+        std::thread_local! {
+            static DROPS: Cell<usize> = const { Cell::new(0) };
+        }
+
+        struct Unit;
+        impl Drop for Unit {
+            fn drop(&mut self) {
+                DROPS.with(|drops| drops.set(drops.get() + 1));
+            }
+        }
+
+        let b = MyBox::new(Unit);
+        // nothing was allocated, the pointer is just well aligned
+        assert_eq!(b.ptr, NonNull::dangling());
+        drop(b);
+        assert_eq!(DROPS.with(Cell::get), 1);
+    }
+
+    #[test]
+    fn deref_mut_and_formatting() {
+        let mut b = MyBox::new(String::from("a"));
+        b.push('b');
+        assert_eq!(format!("{}", b), "ab");
+        assert_eq!(format!("{:?}", b), "\"ab\"");
+    }
+
+    #[test]
+    fn raw_round_trip_drops_once() {
+        let drops = Cell::new(0);
+        let ptr = MyBox::into_raw(MyBox::new(DropCount(&drops)));
+        assert_eq!(drops.get(), 0);
+        // SAFE because ptr came from into_raw and is turned back once
+        let b = unsafe { MyBox::from_raw(ptr) };
+        drop(b);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn trait_objects_through_raw_pointers() {
+        let boxes: Vec<MyBox<dyn fmt::Display>> = vec![
+            // SAFE because every pointer comes from into_raw, it only gained a vtable
+            unsafe { MyBox::from_raw(MyBox::into_raw(MyBox::new(1)) as *mut dyn fmt::Display) },
+            unsafe { MyBox::from_raw(MyBox::into_raw(MyBox::new("two")) as *mut dyn fmt::Display) },
+            unsafe { MyBox::from_raw(MyBox::into_raw(MyBox::new(3.5)) as *mut dyn fmt::Display) },
+        ];
+        let printed: Vec<_> = boxes.iter().map(|b| format!("{}", b)).collect();
+        assert_eq!(printed, ["1", "two", "3.5"]);
+    }
+
+    #[cfg(feature = "nightly")]
+    #[test]
+    fn trait_objects_through_unsizing() {
+        let boxes: Vec<MyBox<dyn fmt::Display>> = vec![
+            MyBox::new(1),
+            MyBox::new(String::from("two")),
+            MyBox::new('3'),
+        ];
+        let printed: Vec<_> = boxes.iter().map(|b| format!("{}", b)).collect();
+        assert_eq!(printed, ["1", "two", "3"]);
+
+        let slice: MyBox<[i32]> = MyBox::new([1, 2, 3]);
+        assert_eq!(slice.len(), 3);
+    }
+
+    #[test]
+    fn leaked_value_stays_alive() {
+        // kept in a static, so Miri sees the leak is on purpose
+        static LEAKED: AtomicPtr<Vec<i32>> = AtomicPtr::new(ptr::null_mut());
+
+        let leaked: &'static mut Vec<i32> = MyBox::leak(MyBox::new(vec![1]));
+        leaked.push(2);
+        LEAKED.store(leaked, Ordering::Relaxed);
+
+        // SAFE because the leaked value is never freed
+        let again = unsafe { &*LEAKED.load(Ordering::Relaxed) };
+        assert_eq!(*again, [1, 2]);
+    }
+}
@@ -1,6 +1,12 @@
 //! # Basic implementation of a Cell mutable container
 //! With most essential info about it
-use std::{cell::UnsafeCell, ptr};
+use core::{
+    cell::UnsafeCell,
+    cmp::Ordering,
+    fmt::{self, Debug},
+    hash::{Hash, Hasher},
+    mem, ptr,
+};
 
 /// # Info
 /// Doesn't impl Sync, so if you have a ref to a cell
@@ -24,26 +30,287 @@ use std::{cell::UnsafeCell, ptr};
 /// # Required to wrap T in UnsafeCell
 /// Because you are never allowed to cast a shared ref to an exclusive ref
 /// in other way than by going through the unsafe cell. It's the only way to implement interior mutability.
-#[derive(Debug)]
-pub struct MyCell<T> {
+///
+/// # Unsized values
+/// T can be a DST like MyCell<[i32]>, as long as it sits behind a pointer.
+/// Methods that move the value in or out still need T to be Sized.
+///
+/// # Required to be repr(transparent)
+/// Guarantees MyCell<T> has the same size, alignment and layout as
+/// UnsafeCell<T>, and so as T itself. from_mut and as_slice_of_cells cast
+/// between the two relying on it, and so can your own code,
+/// e.g a &mut [T] turned into a &[MyCell<T>].
+#[repr(transparent)]
+pub struct MyCell<T: ?Sized> {
     // implied by UnsafeCell
     // impl<T> !Sync for MyCell<T>
     value: UnsafeCell<T>,
 }
 
-impl<T: Copy> MyCell<T> {
-    pub fn new(value: T) -> Self {
+// the layout promise checked at compile time for a few types
+const _: () = {
+    use core::mem::{align_of, size_of};
+
+    macro_rules! assert_same_layout {
+        ($($T:ty),+) => {$(
+            assert!(size_of::<MyCell<$T>>() == size_of::<$T>());
+            assert!(align_of::<MyCell<$T>>() == align_of::<$T>());
+        )+};
+    }
+
+    assert_same_layout!(u8, u64, (u8, u32), [u16; 3], &str, ());
+};
+
+impl<T> MyCell<T> {
+    /// # Info
+    /// const, like std Cell::new, so a cell can be built in a const
+    /// or in a const thread_local! initializer.
+    ///
+    /// A `static` MyCell is still rejected, since MyCell isn't Sync
+    /// and statics can be reached from every thread.
+    pub const fn new(value: T) -> Self {
         MyCell {
             value: UnsafeCell::new(value),
         }
     }
 
+    /// Old value is moved out first and dropped afterwards,
+    /// so its destructor never runs while we are writing into the cell.
     pub fn set(&self, value: T) {
-        unsafe {
-            *self.value.get() = value;
+        drop(self.replace(value));
+    }
+
+    /// Puts the new value in the cell and hands back the old one.
+    ///
+    /// Doesn't need T to be Copy, the old value is moved out of the cell.
+    pub fn replace(&self, value: T) -> T {
+        // SAFE because we never give out a ref to the value,
+        // so nobody else can be looking at it right now
+        unsafe { mem::replace(&mut *self.value.get(), value) }
+    }
+
+    /// # Info
+    /// Swaps the values of two cells.
+    ///
+    /// Doesn't need T to be Copy, the values are only moved,
+    /// so nothing gets dropped. Swapping a cell with itself does nothing.
+    pub fn swap(&self, other: &Self) {
+        if ptr::eq(self, other) {
+            return;
         }
+
+        // SAFE because no refs to either value are ever given out,
+        // and two distinct cells can't overlap
+        unsafe { ptr::swap(self.value.get(), other.value.get()) }
     }
 
+    /// Takes the value out and leaves T::default() in its place.
+    pub fn take(&self) -> T
+    where
+        T: Default,
+    {
+        self.replace(T::default())
+    }
+
+    /// # Info
+    /// Computes the new value from a mutable ref to the old one,
+    /// stores it and hands back the old value.
+    ///
+    /// We can't give the closure a ref into the cell itself, since
+    /// the closure could reach the same cell through a shared ref and set it
+    /// while our &mut is alive. So the old value is taken out first,
+    /// and while the closure runs the cell holds T::default().
+    pub fn replace_with<F: FnOnce(&mut T) -> T>(&self, f: F) -> T
+    where
+        T: Default,
+    {
+        let mut old = self.take();
+        let new = f(&mut old);
+        self.set(new);
+        old
+    }
+
+    /// # Info
+    /// Gives back a clone of the value, for types that can't be Copy,
+    /// e.g a snapshot of a MyCell<Vec<i32>>.
+    ///
+    /// # Required for T to be Default
+    /// Same problem as replace_with: T::clone gets a ref to the value,
+    /// and could reach this cell through it (a MyRc cycle) and set it
+    /// while the ref is alive. So the value is taken out first,
+    /// cloned outside the cell and put back.
+    /// A set done from inside clone is overwritten when we put it back.
+    pub fn get_cloned(&self) -> T
+    where
+        T: Clone + Default,
+    {
+        let value = self.take();
+        let cloned = value.clone();
+        self.set(value);
+        cloned
+    }
+
+    /// Consumes the cell and gives back the value inside.
+    pub fn into_inner(self) -> T {
+        // no borrows to worry about, we own the cell
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> MyCell<T> {
+    /// # Info
+    /// Views an exclusive ref as a shared ref to a cell, like std Cell::from_mut.
+    /// Handy for passing a local you own into code that takes a &MyCell<T>.
+    pub fn from_mut(value: &mut T) -> &MyCell<T> {
+        // SAFE because MyCell<T> is repr(transparent) over UnsafeCell<T>,
+        // which has the same layout as T. The &mut is given up for as long
+        // as the cell ref lives, so the cell is the only way to the value
+        unsafe { &*(value as *mut T as *const MyCell<T>) }
+    }
+
+    /// # Info
+    /// Gives a mutable ref to the value inside.
+    ///
+    /// Sound because it takes &mut self, so the borrow checker
+    /// already guarantees that nobody else can touch the cell
+    /// while the returned ref is alive. No runtime bookkeeping needed.
+    pub fn get_mut(&mut self) -> &mut T {
+        // SAFE because &mut self guarantees we are the only ones
+        // with access to the cell
+        unsafe { &mut *self.value.get() }
+    }
+
+    /// # Info
+    /// Gives a raw pointer to the value inside the cell.
+    ///
+    /// Creating the pointer is safe, but dereferencing it is on you.
+    /// Reading or writing through it while any other ref to the value is live
+    /// (e.g one from get_mut) is UB.
+    pub fn as_ptr(&self) -> *mut T {
+        self.value.get()
+    }
+}
+
+impl<T> MyCell<[T]> {
+    /// # Info
+    /// Views a cell of a slice as a slice of cells,
+    /// so every element can be mutated on its own through a shared ref.
+    pub fn as_slice_of_cells(&self) -> &[MyCell<T>] {
+        // SAFE because MyCell<T> is repr(transparent) over UnsafeCell<T>,
+        // which has the same layout as T. So [T] and [MyCell<T>] look the same
+        // in memory, and the cast keeps the length of the slice.
+        // Going from one shared cell to many shared cells is fine since
+        // none of them ever gives out a ref to its value.
+        unsafe { &*(self as *const MyCell<[T]> as *const [MyCell<T>]) }
+    }
+}
+
+/// # Tuple field accessors
+/// get_N/set_N/replace_N for tuples up to 4 fields, so one field
+/// can be changed without moving the whole tuple in and out.
+///
+/// Same rules as the whole cell methods: get needs the field to be Copy,
+/// set and replace work for any field type since they only move values.
+/// No ref to a field ever leaves the method, and the old value is
+/// dropped only after we're done writing, so its destructor can't see the cell mid update.
+///
+/// For structs with named fields, derive(CellFields) from the derive feature
+/// generates the same accessors per field.
+macro_rules! tuple_fields {
+    ($(($($T:ident),+) { $($idx:tt: $F:ident => $get:ident, $set:ident, $replace:ident;)+ })+) => {$(
+        impl<$($T),+> MyCell<($($T,)+)> {
+            $(
+                pub fn $get(&self) -> $F
+                where
+                    $F: Copy,
+                {
+                    // SAFE because no refs to the value are ever given out
+                    unsafe { (*self.value.get()).$idx }
+                }
+
+                pub fn $set(&self, value: $F) {
+                    drop(self.$replace(value));
+                }
+
+                pub fn $replace(&self, value: $F) -> $F {
+                    // SAFE because no refs to the value are ever given out,
+                    // and nothing else runs while the &mut to the field is alive
+                    unsafe { mem::replace(&mut (*self.value.get()).$idx, value) }
+                }
+            )+
+        }
+    )+};
+}
+
+tuple_fields! {
+    (A, B) {
+        0: A => get_0, set_0, replace_0;
+        1: B => get_1, set_1, replace_1;
+    }
+    (A, B, C) {
+        0: A => get_0, set_0, replace_0;
+        1: B => get_1, set_1, replace_1;
+        2: C => get_2, set_2, replace_2;
+    }
+    (A, B, C, D) {
+        0: A => get_0, set_0, replace_0;
+        1: B => get_1, set_1, replace_1;
+        2: C => get_2, set_2, replace_2;
+        3: D => get_3, set_3, replace_3;
+    }
+}
+
+/// # Counter helpers
+/// increment/decrement and their saturating/wrapping versions
+/// for MyCell of every integer type, instead of `c.set(c.get() + 1)`.
+///
+/// increment and decrement panic on overflow in release builds too,
+/// so a counter going past its bounds is never silently wrapped.
+macro_rules! counter_methods {
+    ($($T:ty)+) => {$(
+        impl MyCell<$T> {
+            /// # Panics
+            /// If the value is already at the max of the type.
+            #[track_caller]
+            pub fn increment(&self) {
+                let n = self.get().checked_add(1).expect("MyCell counter overflowed");
+                self.set(n);
+            }
+
+            /// # Panics
+            /// If the value is already at the min of the type.
+            #[track_caller]
+            pub fn decrement(&self) {
+                let n = self.get().checked_sub(1).expect("MyCell counter underflowed");
+                self.set(n);
+            }
+
+            /// Stays at the max of the type instead of overflowing.
+            pub fn saturating_increment(&self) {
+                self.set(self.get().saturating_add(1));
+            }
+
+            /// Stays at the min of the type instead of underflowing.
+            pub fn saturating_decrement(&self) {
+                self.set(self.get().saturating_sub(1));
+            }
+
+            /// Goes around to the min of the type after the max.
+            pub fn wrapping_increment(&self) {
+                self.set(self.get().wrapping_add(1));
+            }
+
+            /// Goes around to the max of the type after the min.
+            pub fn wrapping_decrement(&self) {
+                self.set(self.get().wrapping_sub(1));
+            }
+        }
+    )+};
+}
+
+counter_methods! { u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize }
+
+impl<T: Copy> MyCell<T> {
     pub fn get(&self) -> T
     where
         T: Copy,
@@ -51,13 +318,491 @@ impl<T: Copy> MyCell<T> {
         unsafe { *self.value.get() }
     }
 
-    pub fn swap(&self, other: &Self) {
+    /// Applies f to the current value and stores the result.
+    pub fn update<F: FnOnce(T) -> T>(&self, f: F) {
+        self.set(f(self.get()));
+    }
+
+    /// Same as update, but gives back the new value that was stored,
+    /// e.g `let next = counter.update_returning(|n| n + 1);`
+    pub fn update_returning<F: FnOnce(T) -> T>(&self, f: F) -> T {
+        let new = f(self.get());
+        self.set(new);
+        new
+    }
+
+    /// # Info
+    /// Conditional update, modeled on the atomic fetch_update.
+    ///
+    /// If f gives back Some(new), new is stored and we get Ok(old).
+    /// If f gives back None, the cell is left alone and we get Err(old).
+    pub fn fetch_update<F: FnMut(T) -> Option<T>>(&self, mut f: F) -> Result<T, T> {
+        let old = self.get();
+        match f(old) {
+            Some(new) => {
+                self.set(new);
+                Ok(old)
+            }
+            None => Err(old),
+        }
+    }
+
+    /// Stores the new value and gives back the previous one in one call,
+    /// so there is no window between a separate get and set.
+    pub fn set_and_get(&self, value: T) -> T {
+        // SAFE because no refs to the value are ever given out,
+        // and T is Copy so reading it out leaves nothing behind to drop
         unsafe {
-            if ptr::eq(self, other) {
-                return;
+            let old = *self.value.get();
+            *self.value.get() = value;
+            old
+        }
+    }
+}
+
+/// # Info
+/// Prints `MyCell { .. }` for every T, the value is left out.
+///
+/// # Required to not print the value
+/// Printing it needs either a copy (only for T: Copy) or a ref to the value
+/// inside, and that ref could alias a set done through the same cell from T's own Debug.
+/// Two impls, one for Copy values and one for the rest, would overlap,
+/// picking between them needs specialization, which stable Rust doesn't have.
+///
+/// So this one works for any T, including the ones that aren't Debug,
+/// and a derive(Debug) on a struct holding a MyCell<String> still compiles.
+/// Use debug_value to print a Copy value.
+impl<T: ?Sized> Debug for MyCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MyCell").finish_non_exhaustive()
+    }
+}
+
+impl<T: Copy + Debug> MyCell<T> {
+    /// # Info
+    /// Something to print instead of the cell, showing the value:
+    /// `MyCell { value: 5 }`. The value is copied out when it's printed.
+    pub fn debug_value(&self) -> impl Debug + '_ {
+        DebugValue(self)
+    }
+}
+
+struct DebugValue<'a, T>(&'a MyCell<T>);
+
+impl<T: Copy + Debug> Debug for DebugValue<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MyCell")
+            .field("value", &self.0.get())
+            .finish()
+    }
+}
+
+impl<T: Default> Default for MyCell<T> {
+    fn default() -> Self {
+        MyCell::new(T::default())
+    }
+}
+
+impl<T> From<T> for MyCell<T> {
+    fn from(value: T) -> Self {
+        MyCell::new(value)
+    }
+}
+
+/// # Info
+/// Gives a new, independent cell holding a copy of the current value.
+impl<T: Copy> Clone for MyCell<T> {
+    fn clone(&self) -> Self {
+        MyCell::new(self.get())
+    }
+}
+
+impl<T: Copy + PartialEq> PartialEq for MyCell<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl<T: Copy + Eq> Eq for MyCell<T> {}
+
+impl<T: Copy + PartialOrd> PartialOrd for MyCell<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.get().partial_cmp(&other.get())
+    }
+}
+
+impl<T: Copy + Ord> Ord for MyCell<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.get().cmp(&other.get())
+    }
+}
+
+/// # Info
+/// Hashes the value currently inside the cell.
+///
+/// If you put a cell in a HashMap/HashSet and then change its value
+/// the hash changes with it and the map invariant is broken,
+/// same as with std Cell.
+impl<T: Copy + Hash> Hash for MyCell<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get().hash(state);
+    }
+}
+
+/// # Info
+/// Serializes the value inside transparently,
+/// so a MyCell<u32> comes out as a bare number.
+#[cfg(feature = "serde")]
+impl<T: Copy + serde::Serialize> serde::Serialize for MyCell<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for MyCell<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(MyCell::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::{collections::HashSet, format, panic, string::String, vec, vec::Vec};
+
+    #[test]
+    fn replace_gives_back_the_old_value() {
+        let cell = MyCell::new(1);
+        assert_eq!(cell.replace(2), 1);
+        assert_eq!(cell.get(), 2);
+
+        let cell = MyCell::new(String::from("a"));
+        assert_eq!(cell.replace(String::from("b")), "a");
+        assert_eq!(cell.into_inner(), "b");
+    }
+
+    #[test]
+    fn take_leaves_the_default_behind() {
+        let cell = MyCell::new(Some(5));
+        assert_eq!(cell.take(), Some(5));
+        assert_eq!(cell.get(), None);
+
+        let cell = MyCell::new(vec![1u8, 2]);
+        assert_eq!(cell.take(), [1, 2]);
+        assert!(cell.into_inner().is_empty());
+    }
+
+    #[test]
+    fn into_inner_gives_back_the_exact_value() {
+        let cell = MyCell::new(String::from("owned"));
+        cell.set(String::from("exact"));
+        assert_eq!(cell.into_inner(), "exact");
+    }
+
+    #[test]
+    fn get_mut_mutates_in_place() {
+        let mut cell = MyCell::new(vec![1]);
+        cell.get_mut().push(2);
+        cell.get_mut().push(3);
+        assert_eq!(cell.into_inner(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn update_stores_the_closure_output() {
+        let counter = MyCell::new(1);
+        counter.update(|n| n + 1);
+        assert_eq!(counter.get(), 2);
+
+        let flag = MyCell::new(false);
+        flag.update(|b| !b);
+        assert!(flag.get());
+        flag.update(|b| !b);
+        assert!(!flag.get());
+    }
+
+    #[test]
+    fn update_returning_gives_the_new_value() {
+        let counter = MyCell::new(1);
+        assert_eq!(counter.update_returning(|n| n + 1), 2);
+        assert_eq!(counter.update_returning(|n| n * 10), 20);
+        assert_eq!(counter.get(), 20);
+    }
+
+    #[test]
+    fn fetch_update_stores_only_accepted_values() {
+        let counter = MyCell::new(254u8);
+        assert_eq!(counter.fetch_update(|n| n.checked_add(1)), Ok(254));
+        assert_eq!(counter.get(), 255);
+        assert_eq!(counter.fetch_update(|n| n.checked_add(1)), Err(255));
+        assert_eq!(counter.get(), 255);
+    }
+
+    #[test]
+    fn set_and_get_gives_back_the_old_value() {
+        let cell = MyCell::new('a');
+        assert_eq!(cell.set_and_get('b'), 'a');
+        assert_eq!(cell.get(), 'b');
+    }
+
+    #[test]
+    fn replace_with_builds_from_the_old_value() {
+        let cell = MyCell::new(vec![1]);
+        let old = cell.replace_with(|v| {
+            v.push(2);
+            v.iter().map(|x| x * 10).collect()
+        });
+        assert_eq!(old, [1, 2]);
+        assert_eq!(cell.into_inner(), [10, 20]);
+    }
+
+    #[test]
+    fn get_cloned_is_independent_of_the_cell() {
+        let cell = MyCell::new(vec![1, 2]);
+        let mut snapshot = cell.get_cloned();
+        snapshot.push(3);
+        assert_eq!(snapshot, [1, 2, 3]);
+        assert_eq!(cell.into_inner(), [1, 2]);
+    }
+
+    #[test]
+    fn swap_exchanges_non_copy_values() {
+        let drops = MyCell::new(0);
+        struct Named<'a>(&'static str, &'a MyCell<i32>);
+        impl Drop for Named<'_> {
+            fn drop(&mut self) {
+                self.1.update(|n| n + 1);
             }
+        }
+
+        let a = MyCell::new(Named("a", &drops));
+        let b = MyCell::new(Named("b", &drops));
+        a.swap(&b);
+        // swapping with itself is a no-op
+        a.swap(&a);
+        assert_eq!(drops.get(), 0);
+        assert_eq!(a.into_inner().0, "b");
+        assert_eq!(b.into_inner().0, "a");
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn default_and_from() {
+        #[derive(Default)]
+        struct Stats {
+            hits: MyCell<usize>,
+            name: MyCell<String>,
+        }
+
+        let stats = Stats::default();
+        assert_eq!(stats.hits.get(), 0);
+        assert_eq!(stats.name.take(), "");
+
+        let cell: MyCell<i32> = 5.into();
+        assert_eq!(cell.get(), 5);
+        assert_eq!(MyCell::from(String::from("x")).into_inner(), "x");
+    }
+
+    #[test]
+    fn clone_is_independent() {
+        let cell = MyCell::new(1);
+        let clone = cell.clone();
+        clone.set(2);
+        assert_eq!(cell.get(), 1);
+        assert_eq!(clone.get(), 2);
+    }
+
+    #[test]
+    fn compares_and_sorts_by_value() {
+        assert_eq!(MyCell::new(1), MyCell::new(1));
+        assert_ne!(MyCell::new(1), MyCell::new(2));
+
+        let mut cells: Vec<_> = [3, 1, 2].into_iter().map(MyCell::new).collect();
+        cells.sort();
+        let sorted: Vec<_> = cells.iter().map(MyCell::get).collect();
+        assert_eq!(sorted, [1, 2, 3]);
+    }
+
+    // fine as long as nothing is set while the cell is in the set
+    #[allow(clippy::mutable_key_type)]
+    #[test]
+    fn hashes_like_the_value() {
+        let mut set: HashSet<MyCell<u32>> = HashSet::new();
+        set.insert(MyCell::new(7));
+        assert!(set.contains(&MyCell::new(7)));
+        assert!(!set.contains(&MyCell::new(8)));
+    }
+
+    #[test]
+    fn as_ptr_reads_the_value() {
+        let cell = MyCell::new(3);
+        // SAFE because nothing else touches the cell while we read
+        assert_eq!(unsafe { *cell.as_ptr() }, cell.get());
+    }
+
+    #[test]
+    fn unsized_through_get_mut() {
+        let mut cell = MyCell::new([1u8, 2, 3]);
+        let slice: &mut MyCell<[u8]> = &mut cell;
+        slice.get_mut()[0] = 9;
+        assert_eq!(cell.into_inner(), [9, 2, 3]);
+    }
+
+    #[test]
+    fn slice_of_cells_mutates_elements() {
+        let cell = MyCell::new([1, 2, 3]);
+        let slice: &MyCell<[i32]> = &cell;
+        let cells = slice.as_slice_of_cells();
+        cells[1].set(20);
+        cells[2].update(|n| n * 10);
+        assert_eq!(cell.into_inner(), [1, 20, 30]);
+    }
+
+    #[test]
+    fn from_mut_writes_to_the_original() {
+        let mut n = 1;
+        MyCell::from_mut(&mut n).set(2);
+        assert_eq!(n, 2);
+
+        let mut v = [1, 2, 3];
+        let cells = MyCell::from_mut(&mut v[..]).as_slice_of_cells();
+        cells[0].swap(&cells[2]);
+        assert_eq!(v, [3, 2, 1]);
+    }
+
+    #[test]
+    fn tuple_fields_keep_the_others() {
+        let cell = MyCell::new((1u32, String::from("a")));
+        cell.set_0(5);
+        assert_eq!(cell.get_0(), 5);
+        assert_eq!(cell.replace_1(String::from("b")), "a");
+        assert_eq!(cell.into_inner(), (5, String::from("b")));
+    }
+
+    #[test]
+    fn counters_saturate_and_wrap() {
+        let counter = MyCell::new(0usize);
+        counter.increment();
+        counter.increment();
+        counter.decrement();
+        assert_eq!(counter.get(), 1);
+
+        let byte = MyCell::new(254u8);
+        byte.saturating_increment();
+        byte.saturating_increment();
+        assert_eq!(byte.get(), u8::MAX);
+        byte.wrapping_increment();
+        assert_eq!(byte.get(), 0);
+        byte.wrapping_decrement();
+        assert_eq!(byte.get(), u8::MAX);
+
+        assert!(panic::catch_unwind(|| MyCell::new(u8::MAX).increment()).is_err());
+        assert!(panic::catch_unwind(|| MyCell::new(0u32).decrement()).is_err());
+    }
 
-            ptr::swap(self.value.get(), other.value.get());
+    // a fresh cell on every use is what the test checks
+    #[allow(
+        clippy::declare_interior_mutable_const,
+        clippy::borrow_interior_mutable_const
+    )]
+    #[test]
+    fn const_cells() {
+        const ZERO: MyCell<u32> = MyCell::new(0);
+        std::thread_local! {
+            static COUNTER: MyCell<u32> = const { MyCell::new(0) };
         }
+
+        // every use of the const is a fresh cell
+        ZERO.set(1);
+        assert_eq!(ZERO.get(), 0);
+        COUNTER.with(|c| c.increment());
+        assert_eq!(COUNTER.with(MyCell::get), 1);
+    }
+
+    #[test]
+    fn debug_leaves_the_value_out() {
+        assert_eq!(format!("{:?}", MyCell::new(5)), "MyCell { .. }");
+        assert_eq!(
+            format!("{:?}", MyCell::new(String::from("text"))),
+            "MyCell { .. }"
+        );
+
+        // still derives for structs holding a non-Copy cell
+        #[derive(Debug)]
+        #[allow(dead_code)]
+        struct Holder {
+            name: MyCell<String>,
+        }
+        let holder = Holder {
+            name: MyCell::new(String::new()),
+        };
+        assert_eq!(format!("{holder:?}"), "Holder { name: MyCell { .. } }");
+    }
+
+    #[test]
+    fn debug_value_prints_copy_value() {
+        assert_eq!(
+            format!("{:?}", MyCell::new(5).debug_value()),
+            "MyCell { value: 5 }"
+        );
+        assert_eq!(
+            format!("{:?}", MyCell::new((1, 'a')).debug_value()),
+            "MyCell { value: (1, 'a') }"
+        );
+        assert_eq!(
+            format!("{:#?}", MyCell::new(true).debug_value()),
+            "MyCell {\n    value: true,\n}"
+        );
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod serde_tests {
+    extern crate std;
+
+    use super::*;
+    use crate::refcell::MyRefCell;
+    use serde::{Deserialize, Serialize};
+    use std::{string::String, vec, vec::Vec};
+
+    #[derive(Serialize, Deserialize)]
+    struct Config {
+        retries: MyCell<u32>,
+        verbose: MyCell<bool>,
+        ratio: MyCell<f64>,
+        names: MyRefCell<Vec<String>>,
+    }
+
+    #[test]
+    fn round_trip_through_json() {
+        let config = Config {
+            retries: MyCell::new(3),
+            verbose: MyCell::new(true),
+            ratio: MyCell::new(0.5),
+            names: MyRefCell::new(vec![String::from("a"), String::from("b")]),
+        };
+
+        // the cells are transparent, only their values show up
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            json,
+            r#"{"retries":3,"verbose":true,"ratio":0.5,"names":["a","b"]}"#
+        );
+
+        let back: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.retries.get(), 3);
+        assert!(back.verbose.get());
+        assert_eq!(back.ratio.get(), 0.5);
+        assert_eq!(*back.names.borrow(), ["a", "b"]);
+    }
+
+    #[test]
+    fn mutably_borrowed_refcell_fails_to_serialize() {
+        let cell = MyRefCell::new(1);
+        let _guard = cell.borrow_mut();
+        let err = serde_json::to_string(&cell).unwrap_err();
+        assert!(std::format!("{}", err).contains("already mutably borrowed"));
     }
 }
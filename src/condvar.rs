@@ -0,0 +1,193 @@
+//! # Basic implementation of a Condvar
+//! With most essential info about it
+
+use std::{
+    collections::VecDeque,
+    mem,
+    sync::{LockResult, PoisonError},
+    thread::{self, Thread},
+};
+
+use crate::{
+    arc::MyArc,
+    mutex::{MyMutex, MyMutexGuard},
+    sync::{AtomicBool, Ordering},
+};
+
+/// # Info
+/// Lets a thread sleep until another thread tells it something changed,
+/// instead of spinning on a MyMutex and checking over and over.
+///
+/// wait gives up the lock, parks the thread, and takes the lock again
+/// once notify_one/notify_all wakes it up.
+///
+/// Like with std Condvar, waking up doesn't mean the condition is true,
+/// another thread might have gotten to the lock first. Always check the
+/// condition in a loop, or use wait_while which does it for you.
+///
+/// # Required to keep a queue of waiters
+/// park/unpark work on a specific thread, so the condvar has to know
+/// who is waiting. Each waiter also gets a notified flag,
+/// since park is allowed to wake up spuriously.
+///
+/// # Common Usage
+/// Producer/consumer queues, where the consumer sleeps until there's work.
+pub struct MyCondvar {
+    waiters: MyMutex<VecDeque<MyArc<Waiter>>>,
+}
+
+struct Waiter {
+    thread: Thread,
+    notified: AtomicBool,
+}
+
+impl MyCondvar {
+    pub fn new() -> Self {
+        MyCondvar {
+            waiters: MyMutex::new(VecDeque::new()),
+        }
+    }
+
+    /// # Info
+    /// Releases the lock of guard and sleeps until notified,
+    /// then takes the lock again and gives back the new guard.
+    ///
+    /// Gives back Err(PoisonError) holding the guard if the mutex got poisoned meanwhile.
+    pub fn wait<'a, T>(&self, guard: MyMutexGuard<'a, T>) -> LockResult<MyMutexGuard<'a, T>> {
+        let waiter = MyArc::new(Waiter {
+            thread: thread::current(),
+            notified: AtomicBool::new(false),
+        });
+
+        // queued before the lock is released, so a notify that comes
+        // right after we let go of the lock can't be missed
+        self.waiters().push_back(waiter.clone());
+
+        let mutex = MyMutexGuard::mutex(&guard);
+        drop(guard);
+
+        // Acquire pairs with the Release in notify,
+        // spurious wake ups just go back to sleep
+        while !waiter.notified.load(Ordering::Acquire) {
+            thread::park();
+        }
+
+        mutex.lock()
+    }
+
+    /// # Info
+    /// Waits for as long as condition gives true, checking it every time we wake up.
+    pub fn wait_while<'a, T, F: FnMut(&mut T) -> bool>(
+        &self,
+        mut guard: MyMutexGuard<'a, T>,
+        mut condition: F,
+    ) -> LockResult<MyMutexGuard<'a, T>> {
+        while condition(&mut guard) {
+            guard = self.wait(guard)?;
+        }
+        Ok(guard)
+    }
+
+    /// Wakes up the thread that has been waiting the longest, if any.
+    pub fn notify_one(&self) {
+        let waiter = self.waiters().pop_front();
+        if let Some(waiter) = waiter {
+            MyCondvar::wake(&waiter);
+        }
+    }
+
+    /// Wakes up every thread currently waiting.
+    pub fn notify_all(&self) {
+        let waiters = mem::take(&mut *self.waiters());
+        for waiter in &waiters {
+            MyCondvar::wake(waiter);
+        }
+    }
+
+    fn wake(waiter: &Waiter) {
+        // Release so the waiter sees everything before the notify
+        waiter.notified.store(true, Ordering::Release);
+        waiter.thread.unpark();
+    }
+
+    /// The queue is never left half updated,
+    /// so a poisoned lock around it is still fine to use.
+    fn waiters(&self) -> MyMutexGuard<'_, VecDeque<MyArc<Waiter>>> {
+        self.waiters.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for MyCondvar {
+    fn default() -> Self {
+        MyCondvar::new()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::{vec, vec::Vec};
+
+    #[test]
+    fn consumer_sleeps_until_the_producer_pushes() {
+        const ITEMS: usize = 5;
+        let queue = MyMutex::new(Vec::new());
+        let condvar = MyCondvar::new();
+
+        let (got, checks) = thread::scope(|s| {
+            let consumer = s.spawn(|| {
+                let mut got = Vec::new();
+                let mut checks = 0;
+                while got.len() < ITEMS {
+                    let guard = queue.lock().unwrap();
+                    let mut guard = condvar
+                        .wait_while(guard, |queue| {
+                            checks += 1;
+                            queue.is_empty()
+                        })
+                        .unwrap();
+                    got.append(&mut guard);
+                }
+                (got, checks)
+            });
+
+            for n in 0..ITEMS {
+                queue.lock().unwrap().push(n);
+                condvar.notify_one();
+            }
+            consumer.join().unwrap()
+        });
+
+        assert_eq!(got, [0, 1, 2, 3, 4]);
+        // one check per wake up plus one per item, a spinning consumer
+        // would check thousands of times. Spurious wake ups are rare
+        assert!(checks <= 4 * ITEMS, "checked {} times", checks);
+    }
+
+    #[test]
+    fn notify_all_wakes_every_waiter() {
+        let queue = MyMutex::new(Vec::new());
+        let condvar = MyCondvar::new();
+
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    let guard = queue.lock().unwrap();
+                    let guard = condvar.wait_while(guard, |queue| queue.len() < 6).unwrap();
+                    assert_eq!(guard.len(), 6);
+                });
+            }
+
+            queue.lock().unwrap().extend(vec![0; 6]);
+            condvar.notify_all();
+        });
+    }
+
+    #[test]
+    fn notify_without_waiters_is_a_no_op() {
+        let condvar = MyCondvar::default();
+        condvar.notify_one();
+        condvar.notify_all();
+        assert!(condvar.waiters().is_empty());
+    }
+}
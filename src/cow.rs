@@ -0,0 +1,160 @@
+//! # Basic implementation of a Cow clone-on-write wrapper
+//! With most essential info about it
+
+use alloc::borrow::ToOwned;
+use core::{borrow::Borrow, fmt, ops::Deref};
+
+/// # Info
+/// Either borrows a value or owns it.
+///
+/// Reading never clones, it just goes through whichever one we have.
+/// The first time we need to mutate a borrowed value it gets cloned
+/// into an owned one, and from then on we mutate our own copy.
+///
+/// Same idea as MyRc::make_mut, but at the borrow level:
+/// instead of a shared count there is a lifetime.
+///
+/// # Required to use ToOwned
+/// The owned form isn't always B itself, e.g &str owns into String
+/// and &[T] owns into Vec<T>. ToOwned tells us which type that is.
+///
+/// # Common Usage
+/// Functions that usually give back their input untouched
+/// but sometimes need to change it, e.g escaping a string.
+pub enum MyCow<'a, B: ?Sized + ToOwned + 'a> {
+    Borrowed(&'a B),
+    Owned(<B as ToOwned>::Owned),
+}
+
+pub use MyCow::{Borrowed, Owned};
+
+impl<B: ?Sized + ToOwned> MyCow<'_, B> {
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self, Borrowed(_))
+    }
+
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+
+    /// # Info
+    /// Gives a mutable ref to the owned value,
+    /// cloning the borrowed one first if that's what we have.
+    pub fn to_mut(&mut self) -> &mut <B as ToOwned>::Owned {
+        if let Borrowed(borrowed) = *self {
+            *self = Owned(borrowed.to_owned());
+        }
+
+        match self {
+            Owned(owned) => owned,
+            Borrowed(_) => unreachable!(),
+        }
+    }
+
+    /// Gives back the owned value, cloning only if we were borrowing.
+    pub fn into_owned(self) -> <B as ToOwned>::Owned {
+        match self {
+            Borrowed(borrowed) => borrowed.to_owned(),
+            Owned(owned) => owned,
+        }
+    }
+}
+
+impl<B: ?Sized + ToOwned> Deref for MyCow<'_, B> {
+    type Target = B;
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Borrowed(borrowed) => borrowed,
+            Owned(owned) => owned.borrow(),
+        }
+    }
+}
+
+/// # Info
+/// A borrowed cow stays borrowed, only an owned one gets cloned.
+impl<B: ?Sized + ToOwned> Clone for MyCow<'_, B> {
+    fn clone(&self) -> Self {
+        match self {
+            Borrowed(borrowed) => Borrowed(borrowed),
+            Owned(owned) => Owned(owned.borrow().to_owned()),
+        }
+    }
+}
+
+impl<B: ?Sized + ToOwned + fmt::Debug> fmt::Debug for MyCow<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<B: ?Sized + ToOwned + fmt::Display> fmt::Display for MyCow<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::{format, string::String, vec, vec::Vec};
+
+    #[test]
+    fn reading_stays_borrowed() {
+        let original = [1, 2, 3];
+        let cow: MyCow<[i32]> = Borrowed(&original);
+
+        assert_eq!(cow.len(), 3);
+        assert_eq!(&*cow, &[1, 2, 3]);
+        assert!(cow.is_borrowed());
+        assert!(!cow.is_owned());
+    }
+
+    #[test]
+    fn to_mut_clones_and_leaves_the_original_alone() {
+        let original = [1, 2, 3];
+        let mut cow: MyCow<[i32]> = Borrowed(&original);
+
+        cow.to_mut().push(4);
+        assert!(cow.is_owned());
+        assert_eq!(&*cow, &[1, 2, 3, 4]);
+        assert_eq!(original, [1, 2, 3]);
+
+        // already owned, mutates the same copy again
+        cow.to_mut()[0] = 10;
+        assert_eq!(&*cow, &[10, 2, 3, 4]);
+    }
+
+    #[test]
+    fn into_owned_from_either_form() {
+        let borrowed: MyCow<str> = Borrowed("hello");
+        let owned: MyCow<str> = Owned(String::from("world"));
+
+        assert_eq!(borrowed.into_owned(), "hello");
+        assert_eq!(owned.into_owned(), "world");
+    }
+
+    #[test]
+    fn clone_keeps_the_form() {
+        let original = vec![1, 2];
+        let borrowed: MyCow<[i32]> = Borrowed(&original);
+        let owned: MyCow<[i32]> = Owned(Vec::from([3, 4]));
+
+        let borrowed_clone = borrowed.clone();
+        assert!(borrowed_clone.is_borrowed());
+        assert!(core::ptr::eq(&*borrowed_clone, &*borrowed));
+
+        let owned_clone = owned.clone();
+        assert!(owned_clone.is_owned());
+        assert_eq!(&*owned_clone, &*owned);
+        assert!(!core::ptr::eq(&*owned_clone, &*owned));
+    }
+
+    #[test]
+    fn formatting_goes_through_the_value() {
+        let cow: MyCow<str> = Borrowed("text");
+        assert_eq!(format!("{cow}"), "text");
+        assert_eq!(format!("{cow:?}"), "\"text\"");
+    }
+}
@@ -0,0 +1,188 @@
+//! # Interner handing out shared MyRc for equal values
+//! With most essential info about it
+
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, Hash, RandomState},
+    vec::Vec,
+};
+
+use crate::rc::{MyRc, MyWeak};
+
+/// # Info
+/// Gives out one shared MyRc per distinct value: interning a value equal
+/// to one that's still alive gives a clone of the MyRc that's already there,
+/// instead of a new allocation.
+///
+/// Interned values can then be compared with MyRc::ptr_eq, which only
+/// compares addresses, instead of comparing the values themselves.
+///
+/// # Required to hold MyWeak
+/// The interner shouldn't be the thing keeping values alive,
+/// otherwise nothing interned would ever be freed.
+/// When the last MyRc goes away the entry is dead, the next intern
+/// of that value makes a new MyRc, and purge drops dead entries.
+///
+/// # Required to key the map on the hash
+/// The only copy of a value is the one inside its MyRc,
+/// so the map can't use the value itself as the key, and a MyWeak can't be
+/// hashed through upgrade either, a dead one would lose its hash.
+/// So the hash is worked out once when the value is interned and kept as the key,
+/// and the values with the same hash are told apart through upgrade.
+///
+/// # Common Usage
+/// Interning strings like identifiers or tag names,
+/// that show up many times but only need to be stored once.
+pub struct MyRcInterner<T> {
+    map: HashMap<u64, Vec<MyWeak<T>>>,
+    hasher: RandomState,
+}
+
+impl<T: Eq + Hash> MyRcInterner<T> {
+    pub fn new() -> Self {
+        MyRcInterner {
+            map: HashMap::new(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// # Info
+    /// Gives the MyRc for a value equal to this one if it's still alive,
+    /// otherwise puts value in a new MyRc and remembers it.
+    pub fn intern(&mut self, value: T) -> MyRc<T> {
+        let entries = self.map.entry(self.hasher.hash_one(&value)).or_default();
+        if let Some(rc) = entries
+            .iter()
+            .filter_map(MyWeak::upgrade)
+            .find(|rc| **rc == value)
+        {
+            return rc;
+        }
+
+        let rc = MyRc::new(value);
+        // a dead entry of the same value is taken out with the others
+        entries.retain(|weak| weak.upgrade().is_some());
+        entries.push(MyRc::downgrade(&rc));
+        rc
+    }
+
+    /// # Info
+    /// Drops the entries whose values are gone.
+    pub fn purge(&mut self) {
+        self.map.retain(|_, entries| {
+            entries.retain(|weak| weak.upgrade().is_some());
+            !entries.is_empty()
+        });
+    }
+
+    /// Number of entries, dead ones included until purge or the next intern of them.
+    pub fn len(&self) -> usize {
+        self.map.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl<T: Eq + Hash> Default for MyRcInterner<T> {
+    fn default() -> Self {
+        MyRcInterner::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::{String, ToString};
+
+    #[test]
+    fn equal_values_share_one_rc() {
+        let mut interner = MyRcInterner::new();
+
+        let a = interner.intern("tag".to_string());
+        let b = interner.intern("tag".to_string());
+        let c = interner.intern("other".to_string());
+
+        assert!(MyRc::ptr_eq(&a, &b));
+        assert!(!MyRc::ptr_eq(&a, &c));
+        assert_eq!(MyRc::strong_count(&a), 2);
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn interner_does_not_keep_values_alive() {
+        let mut interner: MyRcInterner<String> = MyRcInterner::default();
+        assert!(interner.is_empty());
+
+        let a = interner.intern("gone".to_string());
+        let weak = MyRc::downgrade(&a);
+        drop(a);
+        assert!(weak.upgrade().is_none());
+
+        // the dead entry stays until purge
+        assert_eq!(interner.len(), 1);
+        interner.purge();
+        assert!(interner.is_empty());
+    }
+
+    #[test]
+    fn purge_keeps_live_entries() {
+        let mut interner = MyRcInterner::new();
+
+        let kept = interner.intern(1);
+        drop(interner.intern(2));
+        interner.purge();
+
+        assert_eq!(interner.len(), 1);
+        assert!(MyRc::ptr_eq(&kept, &interner.intern(1)));
+    }
+
+    #[test]
+    fn values_dont_need_clone() {
+        #[derive(PartialEq, Eq, Hash)]
+        struct Name(String);
+
+        let mut interner = MyRcInterner::new();
+        let a = interner.intern(Name("a".to_string()));
+        let b = interner.intern(Name("a".to_string()));
+        assert!(MyRc::ptr_eq(&a, &b));
+    }
+
+    #[test]
+    fn values_with_the_same_hash_stay_apart() {
+        // every value hashes the same
+        #[derive(PartialEq, Eq)]
+        struct Collide(i32);
+
+        impl Hash for Collide {
+            fn hash<H: std::hash::Hasher>(&self, _: &mut H) {}
+        }
+
+        let mut interner = MyRcInterner::new();
+        let one = interner.intern(Collide(1));
+        let two = interner.intern(Collide(2));
+        assert!(!MyRc::ptr_eq(&one, &two));
+        assert!(MyRc::ptr_eq(&one, &interner.intern(Collide(1))));
+        assert_eq!(interner.len(), 2);
+
+        drop(one);
+        interner.purge();
+        assert_eq!(interner.len(), 1);
+        assert_eq!(interner.intern(Collide(2)).0, 2);
+    }
+
+    #[test]
+    fn dead_entry_is_replaced_on_the_next_intern() {
+        let mut interner = MyRcInterner::new();
+
+        let first = interner.intern(7);
+        let weak = MyRc::downgrade(&first);
+        drop(first);
+
+        let second = interner.intern(7);
+        assert_eq!(*second, 7);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(interner.len(), 1);
+    }
+}
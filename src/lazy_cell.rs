@@ -0,0 +1,101 @@
+//! # Basic implementation of a LazyCell
+//! With most essential info about it
+
+use core::ops::Deref;
+
+use crate::{cell::MyCell, once_cell::MyOnceCell};
+
+/// # Info
+/// A MyOnceCell that knows how to fill itself.
+///
+/// The init closure is stored next to the cell and runs
+/// on the first deref, every deref after that just reads the cached value.
+///
+/// # Required to keep F in a MyCell
+/// force only has &self, but it has to move the closure out to call it,
+/// since FnOnce can only be called by value.
+///
+/// # Common Usage
+/// Values that are expensive to build and maybe never needed,
+/// e.g a parsed config or a lookup table.
+pub struct MyLazyCell<T, F = fn() -> T> {
+    cell: MyOnceCell<T>,
+    init: MyCell<Option<F>>,
+}
+
+impl<T, F: FnOnce() -> T> MyLazyCell<T, F> {
+    pub fn new(f: F) -> Self {
+        MyLazyCell {
+            cell: MyOnceCell::new(),
+            init: MyCell::new(Some(f)),
+        }
+    }
+
+    /// # Info
+    /// Runs the init closure if it hasn't run yet and gives a ref to the value.
+    ///
+    /// # Panics
+    /// If an earlier init panicked, the closure is gone and there's no value to give.
+    pub fn force(this: &MyLazyCell<T, F>) -> &T {
+        this.cell.get_or_init(|| match this.init.take() {
+            Some(f) => f(),
+            None => panic!("MyLazyCell instance has previously been poisoned"),
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for MyLazyCell<T, F> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        MyLazyCell::force(self)
+    }
+}
+
+impl<T: Default> Default for MyLazyCell<T> {
+    fn default() -> Self {
+        MyLazyCell::new(T::default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        vec::Vec,
+    };
+
+    #[test]
+    fn init_runs_once_across_derefs() {
+        let runs = MyCell::new(0usize);
+        let lazy = MyLazyCell::new(|| {
+            runs.increment();
+            (0..4).collect::<Vec<i32>>()
+        });
+        assert_eq!(runs.get(), 0);
+
+        for _ in 0..10 {
+            assert_eq!(lazy.len(), 4);
+        }
+        assert_eq!(*MyLazyCell::force(&lazy), [0, 1, 2, 3]);
+        assert_eq!(runs.get(), 1);
+    }
+
+    #[test]
+    fn panicking_init_poisons_the_cell() {
+        let lazy: MyLazyCell<i32, _> = MyLazyCell::new(|| panic!("no value"));
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| *lazy)).is_err());
+
+        let err = panic::catch_unwind(AssertUnwindSafe(|| *lazy)).unwrap_err();
+        let message = err.downcast_ref::<&str>().unwrap();
+        assert!(message.contains("poisoned"));
+    }
+
+    #[test]
+    fn default_uses_the_default_value() {
+        let lazy: MyLazyCell<u32> = MyLazyCell::default();
+        assert_eq!(*lazy, 0);
+    }
+}
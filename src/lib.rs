@@ -1,3 +1,85 @@
+//! # A study on rust smart pointers
+//! With their essential concepts documented.
+//!
+//! # no_std
+//! The crate is no_std, it only needs core and alloc.
+//! Everything works without std except MyMutex, MyCondvar and MyRcInterner,
+//! because poisoning needs to know if the thread is panicking,
+//! waiting needs to park the thread and the interner needs a HashMap.
+//! They live behind the std feature, which is on by default.
+#![no_std]
+#![allow(non_snake_case)]
+#![cfg_attr(feature = "nightly", feature(coerce_unsized, unsize))]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod arc;
+pub mod atomic_cell;
+pub mod boxed;
 pub mod cell;
+#[cfg(feature = "std")]
+pub mod condvar;
+pub mod cow;
+#[cfg(feature = "std")]
+pub mod interner;
+pub mod lazy_cell;
+#[cfg(feature = "std")]
+pub mod mutex;
+pub mod once_cell;
+pub mod prelude;
 pub mod rc;
+pub mod rc_cell;
 pub mod refcell;
+pub mod rwlock;
+mod sync;
+pub mod tree;
+
+#[cfg(feature = "derive")]
+pub use cell_fields_derive::CellFields;
+
+/// # Info
+/// Kills the process right away, without unwinding.
+///
+/// Used when a ref count would overflow, since unwinding
+/// would run destructors that might still rely on the broken count.
+#[cfg(feature = "std")]
+#[cfg_attr(test, allow(dead_code))]
+fn abort() -> ! {
+    std::process::abort()
+}
+
+/// # Info
+/// Without std there is no process to abort, but a panic while already
+/// panicking is always an abort, so we panic from inside a destructor
+/// that runs during the unwind of another panic.
+#[cfg(not(feature = "std"))]
+#[cfg_attr(test, allow(dead_code))]
+fn abort() -> ! {
+    struct Abort;
+    impl Drop for Abort {
+        fn drop(&mut self) {
+            panic!("aborting");
+        }
+    }
+
+    let _abort = Abort;
+    panic!("aborting");
+}
+
+/// # Info
+/// Called by MyRc and MyArc when a ref count would overflow.
+#[cfg(not(test))]
+fn count_overflow() -> ! {
+    abort()
+}
+
+/// # Info
+/// An abort can't be caught by the test harness, so the crate's own tests
+/// get a panic instead, and can check the count was never wrapped.
+#[cfg(test)]
+fn count_overflow() -> ! {
+    panic!("reference count overflow")
+}
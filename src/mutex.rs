@@ -0,0 +1,317 @@
+//! # Basic implementation of a Mutex spin lock
+//! With most essential info about it
+
+use crate::sync::{hint, AtomicBool, Ordering};
+use std::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::{LockResult, PoisonError, TryLockError, TryLockResult},
+    thread,
+};
+
+/// # Required to wrap value T in UnsafeCell
+/// Because you are never allowed to cast a shared ref to an exclusive ref
+/// in other way than by going through the unsafe cell.
+///
+/// # Info
+/// Same idea as MyRefCell, but for many threads:
+/// instead of failing when the value is already borrowed,
+/// lock spins until the thread holding the lock releases it.
+///
+/// Only hands out exclusive access, one thread at a time.
+///
+/// # Common Usage
+/// Mutating shared data from multiple threads,
+/// e.g a counter behind a MyArc.
+///
+/// # Poisoning
+/// If a thread panics while holding the lock, the value may be left
+/// half updated, so the mutex gets marked as poisoned.
+/// Every lock after that still gets the guard, but wrapped in a PoisonError,
+/// so the caller has to decide if the data can still be trusted.
+pub struct MyMutex<T> {
+    locked: AtomicBool,
+    poisoned: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+/// # Required to impl Sync by hand
+/// UnsafeCell is not Sync, so the compiler won't derive it.
+///
+/// Only one thread at a time gets to the value, so T doesn't need to be Sync,
+/// but it has to be Send, since the value is effectively handed over
+/// to whichever thread takes the lock.
+unsafe impl<T: Send> Sync for MyMutex<T> {}
+
+impl<T> MyMutex<T> {
+    pub fn new(value: T) -> Self {
+        MyMutex {
+            locked: AtomicBool::new(false),
+            poisoned: AtomicBool::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// # Info
+    /// Spins until the lock is free, then takes it.
+    /// The lock is released when the guard goes out of scope.
+    ///
+    /// Gives back Err(PoisonError) holding the guard if the mutex is poisoned.
+    pub fn lock(&self) -> LockResult<MyMutexGuard<'_, T>> {
+        loop {
+            match self.try_lock() {
+                Ok(guard) => return Ok(guard),
+                Err(TryLockError::Poisoned(err)) => return Err(err),
+                Err(TryLockError::WouldBlock) => {}
+            }
+
+            // wait until it looks free before trying the CAS again,
+            // so we don't keep taking the cache line away from the owner
+            while self.locked.load(Ordering::Relaxed) {
+                hint::spin_loop();
+            }
+        }
+    }
+
+    /// # Info
+    /// Takes the lock if it's free right now, WouldBlock otherwise.
+    pub fn try_lock(&self) -> TryLockResult<MyMutexGuard<'_, T>> {
+        // Acquire so we see everything the previous owner did with the value
+        if self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(TryLockError::WouldBlock);
+        }
+
+        let guard = MyMutexGuard {
+            mutex: self,
+            panicking: thread::panicking(),
+            _marker: PhantomData,
+        };
+
+        if self.is_poisoned() {
+            Err(TryLockError::Poisoned(PoisonError::new(guard)))
+        } else {
+            Ok(guard)
+        }
+    }
+
+    /// # Info
+    /// Relaxed is enough, the flag is only written while holding the lock,
+    /// and taking the lock already syncs with the thread that wrote it.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    /// # Info
+    /// Marks the mutex as healthy again,
+    /// for when the caller has checked or fixed the value.
+    pub fn clear_poison(&self) {
+        self.poisoned.store(false, Ordering::Relaxed);
+    }
+
+    /// # Info
+    /// Consumes the mutex and gives back the value, without locking.
+    /// We own the mutex, so no guard can be alive.
+    ///
+    /// Still reports poisoning the same way lock does.
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.is_poisoned();
+        let value = self.value.into_inner();
+        if poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// # Info
+    /// Gives a mutable ref to the value, without locking.
+    /// &mut self already guarantees at compile time that no guard is alive.
+    ///
+    /// Still reports poisoning the same way lock does.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.is_poisoned();
+        let value = self.value.get_mut();
+        if poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+}
+
+/// # Guard type as output for lock method
+/// We need this type to release the lock after it goes out of scope
+///
+/// # Required to use PhantomData
+/// Behaves like &mut T, so sharing the guard between threads
+/// has to require T: Sync, which &MyMutex<T> alone doesn't.
+///
+/// # Required to remember panicking
+/// Only a panic that started while the guard was alive poisons the mutex.
+/// A guard taken inside a Drop during unwinding shouldn't poison it on release.
+pub struct MyMutexGuard<'mutex, T> {
+    mutex: &'mutex MyMutex<T>,
+    panicking: bool,
+    _marker: PhantomData<&'mutex mut T>,
+}
+
+impl<'mutex, T> MyMutexGuard<'mutex, T> {
+    /// The mutex this guard locks, so MyCondvar can lock it again after waiting.
+    pub(crate) fn mutex(this: &MyMutexGuard<'mutex, T>) -> &'mutex MyMutex<T> {
+        this.mutex
+    }
+}
+
+impl<T> Deref for MyMutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFE because guard is only created when we hold the lock
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T> DerefMut for MyMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFE because guard is only created when we hold the lock
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T> Drop for MyMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        if !self.panicking && thread::panicking() {
+            self.mutex.poisoned.store(true, Ordering::Relaxed);
+        }
+
+        // Release so the next owner sees everything we did with the value
+        self.mutex.locked.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::arc::MyArc;
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        string::String,
+        vec,
+    };
+
+    #[test]
+    fn counter_from_many_threads() {
+        const THREADS: usize = 4;
+        let iters = if cfg!(miri) { 50 } else { 1000 };
+
+        let counter = MyMutex::new(0);
+        thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| {
+                    for _ in 0..iters {
+                        *counter.lock().unwrap() += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(counter.into_inner().unwrap(), THREADS * iters);
+    }
+
+    #[test]
+    fn try_lock_would_block_while_held() {
+        let mutex = MyMutex::new(1);
+        let guard = mutex.lock().unwrap();
+        assert!(matches!(mutex.try_lock(), Err(TryLockError::WouldBlock)));
+        drop(guard);
+        assert_eq!(*mutex.try_lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn panic_while_locked_poisons_for_other_threads() {
+        let mutex = MyArc::new(MyMutex::new(vec![1]));
+        let other = mutex.clone();
+        let result = thread::spawn(move || {
+            let mut guard = other.lock().unwrap();
+            guard.push(2);
+            panic!("half way through");
+        })
+        .join();
+        assert!(result.is_err());
+
+        assert!(mutex.is_poisoned());
+        let seen = thread::scope(|s| {
+            s.spawn(|| match mutex.lock() {
+                Err(poisoned) => poisoned.into_inner().clone(),
+                Ok(_) => panic!("expected the mutex to be poisoned"),
+            })
+            .join()
+            .unwrap()
+        });
+        // the guard is still handed out, the value is as the panic left it
+        assert_eq!(seen, [1, 2]);
+
+        mutex.clear_poison();
+        assert!(mutex.lock().is_ok());
+    }
+
+    #[test]
+    fn lock_taken_during_unwinding_does_not_poison() {
+        struct LockOnDrop<'a>(&'a MyMutex<i32>);
+        impl Drop for LockOnDrop<'_> {
+            fn drop(&mut self) {
+                *self.0.lock().unwrap() += 1;
+            }
+        }
+
+        let mutex = MyMutex::new(0);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _on_drop = LockOnDrop(&mutex);
+            panic!("unwinding");
+        }));
+        assert!(result.is_err());
+        assert!(!mutex.is_poisoned());
+        assert_eq!(*mutex.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn into_inner_and_get_mut_after_the_guards() {
+        let mut mutex = MyMutex::new(String::from("a"));
+        mutex.lock().unwrap().push('b');
+        mutex.get_mut().unwrap().push('c');
+        assert_eq!(mutex.into_inner().unwrap(), "abc");
+
+        // poisoning shows up on both too
+        let mut mutex = MyMutex::new(1);
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = mutex.lock().unwrap();
+            panic!("poison it");
+        }));
+        assert!(mutex.get_mut().is_err());
+        assert_eq!(mutex.into_inner().unwrap_err().into_inner(), 1);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use crate::arc::MyArc;
+    use loom::thread;
+
+    #[test]
+    fn lock_from_two_threads() {
+        loom::model(|| {
+            let mutex = MyArc::new(MyMutex::new(0));
+            let other = mutex.clone();
+
+            let t = thread::spawn(move || *other.lock().unwrap() += 1);
+            *mutex.lock().unwrap() += 1;
+            t.join().unwrap();
+
+            assert_eq!(*mutex.lock().unwrap(), 2);
+        });
+    }
+}
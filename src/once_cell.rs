@@ -0,0 +1,153 @@
+//! # Basic implementation of a OnceCell write-once container
+//! With most essential info about it
+
+use core::cell::UnsafeCell;
+
+/// # Info
+/// Starts empty and can be set at most once,
+/// after that the value can only be looked at.
+///
+/// Unlike MyCell it gives out shared refs to the value inside,
+/// which is fine because once the value is in it never moves or changes
+/// until the cell itself goes away.
+///
+/// Doesn't impl Sync, same as MyCell.
+///
+/// # Common Usage
+/// Lazy initialization, e.g config that's computed on first use
+/// and then read from many places.
+///
+/// # Required to wrap Option<T> in UnsafeCell
+/// Filling the cell writes through a shared ref,
+/// so the UnsafeCell is needed for interior mutability.
+pub struct MyOnceCell<T> {
+    // implied by UnsafeCell
+    // impl<T> !Sync for MyOnceCell<T>
+    value: UnsafeCell<Option<T>>,
+}
+
+impl<T> MyOnceCell<T> {
+    pub fn new() -> Self {
+        MyOnceCell {
+            value: UnsafeCell::new(None),
+        }
+    }
+
+    /// Gives a ref to the value, or None if the cell is still empty.
+    pub fn get(&self) -> Option<&T> {
+        // SAFE because the only write happens while the cell is empty,
+        // and an empty cell has no refs to the value out there
+        unsafe { (*self.value.get()).as_ref() }
+    }
+
+    /// # Info
+    /// Fills the cell, or gives the value back if it was already full.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        if self.get().is_some() {
+            return Err(value);
+        }
+
+        // SAFE because the cell is empty, so no ref from get can be alive
+        unsafe { *self.value.get() = Some(value) };
+        Ok(())
+    }
+
+    /// # Info
+    /// Gives a ref to the value, filling the cell with f() first if it's empty.
+    ///
+    /// # Panics
+    /// If f fills the same cell while it runs (reentrant init),
+    /// since we'd have to drop a value somebody may already have a ref to.
+    pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+
+        let value = f();
+        assert!(self.set(value).is_ok(), "reentrant init");
+        self.get().unwrap()
+    }
+
+    /// Gives a mutable ref to the value, &mut self guarantees no other refs exist.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.value.get_mut().as_mut()
+    }
+
+    /// Takes the value out and leaves the cell empty, so it can be set again.
+    pub fn take(&mut self) -> Option<T> {
+        self.value.get_mut().take()
+    }
+
+    /// Consumes the cell and gives back the value, if there is one.
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+}
+
+impl<T> Default for MyOnceCell<T> {
+    fn default() -> Self {
+        MyOnceCell::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::cell::MyCell;
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        string::String,
+    };
+
+    #[test]
+    fn second_set_fails() {
+        let cell = MyOnceCell::new();
+        assert_eq!(cell.get(), None);
+        assert_eq!(cell.set(1), Ok(()));
+        assert_eq!(cell.set(2), Err(2));
+        assert_eq!(cell.get(), Some(&1));
+    }
+
+    #[test]
+    fn get_or_init_runs_the_closure_once() {
+        let cell = MyOnceCell::new();
+        let runs = MyCell::new(0usize);
+        let first = cell.get_or_init(|| {
+            runs.increment();
+            String::from("value")
+        });
+        let second = cell.get_or_init(|| {
+            runs.increment();
+            String::from("other")
+        });
+        assert_eq!(runs.get(), 1);
+        assert_eq!(first, "value");
+        assert!(core::ptr::eq(first, second));
+    }
+
+    #[test]
+    fn reentrant_init_panics() {
+        let cell = MyOnceCell::new();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            cell.get_or_init(|| {
+                let _ = cell.set(1);
+                2
+            })
+        }));
+        assert!(result.is_err());
+        // the value from the inner set stays, nobody saw the outer one
+        assert_eq!(cell.get(), Some(&1));
+    }
+
+    #[test]
+    fn take_empties_for_another_set() {
+        let mut cell = MyOnceCell::default();
+        cell.set(1).unwrap();
+        *cell.get_mut().unwrap() += 1;
+        assert_eq!(cell.take(), Some(2));
+        assert!(cell.set(3).is_ok());
+        assert_eq!(cell.into_inner(), Some(3));
+    }
+}
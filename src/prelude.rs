@@ -0,0 +1,99 @@
+//! # All the pointer and cell types in one place
+//! `use Pointers_Study_With_Core_Concepts::prelude::*;` brings in every main type,
+//! so there is no need to reach into the individual modules.
+
+pub use crate::arc::{ArcWeak, MyArc, MyArcSwap};
+pub use crate::atomic_cell::MySyncCell;
+pub use crate::boxed::MyBox;
+pub use crate::cell::MyCell;
+#[cfg(feature = "std")]
+pub use crate::condvar::MyCondvar;
+pub use crate::cow::MyCow;
+#[cfg(feature = "std")]
+pub use crate::interner::MyRcInterner;
+pub use crate::lazy_cell::MyLazyCell;
+#[cfg(feature = "std")]
+pub use crate::mutex::MyMutex;
+pub use crate::once_cell::MyOnceCell;
+pub use crate::rc::{MyRc, MyWeak};
+pub use crate::rc_cell::MyRcCell;
+pub use crate::refcell::{MyReentrantRefCell, MyRefCell, Ref, RefMut};
+pub use crate::rwlock::MyRwLock;
+pub use crate::tree::MyTreeNode;
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    extern crate std;
+
+    use crate::prelude::*;
+    use core::cell::Cell;
+
+    #[test]
+    fn one_of_each_type() {
+        let cell = MyCell::new(1);
+        let refcell = MyRefCell::new(2);
+        let reading: Ref<i32> = refcell.borrow();
+        assert_eq!(cell.get() + *reading, 3);
+        drop(reading);
+        let mut writing: RefMut<i32> = refcell.borrow_mut();
+        *writing = 3;
+        drop(writing);
+
+        let reentrant = MyReentrantRefCell::new(4);
+        assert_eq!(*reentrant.borrow(), 4);
+
+        let rc = MyRc::new(5);
+        let weak: MyWeak<i32> = MyRc::downgrade(&rc);
+        assert_eq!(*weak.upgrade().unwrap(), 5);
+        let rc_cell = MyRcCell::new(6);
+        assert_eq!(*rc_cell.borrow(), 6);
+
+        let arc = MyArc::new(7);
+        let arc_weak: ArcWeak<i32> = MyArc::downgrade(&arc);
+        assert_eq!(*arc_weak.upgrade().unwrap(), 7);
+        let swap = MyArcSwap::new(arc.clone());
+        assert_eq!(*swap.load(), 7);
+        let sync_cell = MySyncCell::new(8);
+        assert_eq!(sync_cell.load(), 8);
+        let rwlock = MyRwLock::new(9);
+        assert_eq!(*rwlock.read(), 9);
+
+        let boxed = MyBox::new(10);
+        assert_eq!(*boxed, 10);
+        let cow: MyCow<str> = MyCow::Borrowed("eleven");
+        assert_eq!(&*cow, "eleven");
+
+        let once = MyOnceCell::new();
+        assert_eq!(*once.get_or_init(|| 12), 12);
+        let calls = Cell::new(0);
+        let lazy = MyLazyCell::new(|| {
+            calls.set(calls.get() + 1);
+            13
+        });
+        assert_eq!(*lazy, 13);
+        assert_eq!(calls.get(), 1);
+
+        let root = MyTreeNode::new(14);
+        root.add_child(MyTreeNode::new(15));
+        assert_eq!(*root.children()[0].value(), 15);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn one_of_each_std_type() {
+        use std::string::ToString;
+
+        let mutex = MyMutex::new(1);
+        *mutex.lock().unwrap() += 1;
+        assert_eq!(*mutex.lock().unwrap(), 2);
+
+        let condvar = MyCondvar::new();
+        condvar.notify_one();
+        condvar.notify_all();
+
+        let mut interner = MyRcInterner::new();
+        let a = interner.intern("name".to_string());
+        let b = interner.intern("name".to_string());
+        assert!(MyRc::ptr_eq(&a, &b));
+    }
+}
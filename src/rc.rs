@@ -1,13 +1,32 @@
 //! Basic implementation of Rc shared pointer
 
 use crate::cell::MyCell;
-use std::{marker::PhantomData, ops::Deref, ptr::NonNull};
+use ::alloc::{
+    alloc::{self, Layout},
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    borrow::Borrow,
+    cmp::Ordering,
+    error::Error,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    mem::{self, ManuallyDrop},
+    ops::Deref,
+    pin::Pin,
+    ptr::{self, NonNull},
+};
+
+#[cfg(feature = "nightly")]
+use core::{marker::Unsize, ops::CoerceUnsized};
 
 /// # Required to use PhantomData
 /// It tells the compiler that when you drop the Rc
 /// an RcInner<T> might be dropped, and you need to check that.
 /// In other words, it marks the RcInner<T> as owned by the Rc.
-
+///
 /// # Info
 /// Doesn't impl Sync and Send because
 /// if you would send the Rc to a different thread then both
@@ -24,66 +43,1724 @@ use std::{marker::PhantomData, ops::Deref, ptr::NonNull};
 /// one element be present in multiple places
 /// e.g when you have something like config
 /// and you dont want to make many copies of it.
-
-pub struct MyRc<T> {
+///
+/// # Trait objects
+/// With the nightly feature, MyRc<T> coerces to MyRc<dyn Trait>
+/// the same way Rc does, see the CoerceUnsized impl below.
+///
+/// # Zero sized values
+/// MyRc<()> or any other zero sized value takes no space in the allocation,
+/// so all that gets allocated is the two counts, see the assert below RcInner.
+///
+/// Every MyRc::new still gets its own counts. One header shared by all of them
+/// would save the allocation too, but then unrelated MyRc would share counts:
+/// strong_count, get_mut, try_unwrap, make_mut and ptr_eq would all see each other,
+/// and a MyWeak could upgrade to a MyRc made after its own was dropped.
+///
+/// # Allocators
+/// A is where the RcInner comes from, the global allocator by default.
+/// MyRc::new_in takes any other RcAllocator, e.g a bump or pool allocator,
+/// and the same allocator frees the RcInner once the last MyRc/MyWeak is gone.
+pub struct MyRc<T: ?Sized, A: RcAllocator = Global> {
     inner: NonNull<RcInner<T>>,
+    alloc: A,
     _marker: PhantomData<RcInner<T>>,
 }
 
 /// # Struct to store T value and ref count of the Rc
-pub struct RcInner<T> {
-    value: T,
+///
+/// Both counts live inline next to the value, so MyRc and MyWeak
+/// share this one allocation, there is no separate block for the counts.
+///
+/// # Required to wrap value T in ManuallyDrop
+/// The value is dropped when the last MyRc goes away,
+/// but the allocation stays alive until the last MyWeak goes away too.
+/// Freeing the box later must not drop the value a second time.
+///
+/// # Required to use repr(C)
+/// With an unsized T, like MyRc<[T]>, we lay out the allocation by hand:
+/// the counts first, then the value. repr(C) makes sure the compiler
+/// puts the fields in the same order.
+#[repr(C)]
+pub struct RcInner<T: ?Sized> {
     ref_count: MyCell<usize>,
+    // all MyRc together hold one weak ref, so the allocation
+    // can't be freed by a MyWeak while the value is still alive
+    weak_count: MyCell<usize>,
+    value: ManuallyDrop<T>,
+}
+
+// a zero sized value adds nothing, the allocation is only the counts
+const _: () = assert!(mem::size_of::<RcInner<()>>() == 2 * mem::size_of::<usize>());
+
+/// # Required to check for overflow
+/// The only way to get that many refs is to mem::forget clones in a loop.
+/// If the count wrapped around, the next drop would free the value
+/// while the forgotten refs still point to it (use after free).
+///
+/// Panicking is not good enough, someone could catch the unwind and
+/// keep going, so we abort the process same as std does.
+fn increment(count: &MyCell<usize>) {
+    let n = count.get();
+    if n == MAX_COUNT {
+        crate::count_overflow();
+    }
+    count.set(n + 1);
+}
+
+#[cfg(not(test))]
+const MAX_COUNT: usize = usize::MAX;
+
+/// Lowered in tests, so the overflow branch is reachable
+/// without forgetting usize::MAX clones.
+#[cfg(test)]
+const MAX_COUNT: usize = 1 << 10;
+
+/// # Error returned by try_new
+/// The allocator couldn't give us memory for the MyRc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError;
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("memory allocation failed")
+    }
+}
+
+impl Error for AllocError {}
+
+/// # Info
+/// Where MyRc and MyWeak get their memory from, and give it back to.
+///
+/// A small stand-in for the unstable core Allocator trait, so MyRc<T, A>
+/// also works on stable. With the allocator_api feature every
+/// core Allocator is an RcAllocator too.
+///
+/// The allocator is stored in every MyRc/MyWeak, so handles to a pool
+/// should be cheap to clone, e.g a ref or a MyRc to the pool.
+///
+/// # Safety
+/// Memory from allocate has to fit the layout and stay valid until it's given
+/// to deallocate, on this allocator or any clone of it.
+pub unsafe trait RcAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError>;
+
+    /// # Safety
+    /// ptr has to come from allocate on this allocator (or a clone of it),
+    /// with the same layout, and is not used afterwards.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+}
+
+/// # Info
+/// The global allocator, the one Box and Vec use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Global;
+
+unsafe impl RcAllocator for Global {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        // SAFE because an RcInner is never zero sized, the counts are always there
+        NonNull::new(unsafe { alloc::alloc(layout) }).ok_or(AllocError)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        // SAFE because the caller guarantees ptr came from allocate with this layout
+        unsafe { alloc::dealloc(ptr.as_ptr(), layout) }
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+unsafe impl<A: core::alloc::Allocator> RcAllocator for A {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        core::alloc::Allocator::allocate(self, layout)
+            .map(NonNull::cast)
+            .map_err(|_| AllocError)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        // SAFE because the caller guarantees ptr came from allocate with this layout
+        unsafe { core::alloc::Allocator::deallocate(self, ptr, layout) }
+    }
+}
+
+/// # Info
+/// Gives back one weak ref, and frees the allocation through alloc
+/// if it was the last one. Shared by the Drop of MyWeak and MyRc.
+///
+/// # Safety
+/// inner has to be a live allocation from alloc (or a clone of it) with this layout,
+/// and the caller has to own the weak ref it gives back.
+unsafe fn release_weak<T: ?Sized, A: RcAllocator>(
+    inner: NonNull<RcInner<T>>,
+    layout: Layout,
+    alloc: &A,
+) {
+    // SAFE because we still own a weak ref, so the allocation is alive
+    let counts = unsafe { Counts::new(inner) };
+    let current_weak = counts.weak_count.get();
+    counts.weak_count.set(current_weak - 1);
+    if current_weak != 1 {
+        return;
+    }
+
+    //SAFE because the value was already dropped by the last rc
+    //and nothing drops it again, it's in ManuallyDrop.
+    //The layout is passed in instead of read off the value, so no ref
+    //to the dropped value is made. The pointer still has the provenance
+    //of the whole allocation, it was never narrowed down by going through a ref
+    unsafe { alloc.deallocate(inner.cast(), layout) };
+    leak_check::unregister();
+}
+
+/// # Leak check registry
+/// With the leak_check feature, debug builds count the RcInner allocations
+/// that are alive on the current thread, see MyRc::report_leaked.
+///
+/// Thread local is enough since MyRc is not Send,
+/// an allocation is always freed on the thread that made it.
+#[cfg(all(feature = "leak_check", debug_assertions))]
+mod leak_check {
+    use core::cell::Cell;
+
+    std::thread_local! {
+        static LIVE: Cell<usize> = const { Cell::new(0) };
+    }
+
+    pub(super) fn register() {
+        LIVE.with(|live| live.set(live.get() + 1));
+    }
+
+    pub(super) fn unregister() {
+        LIVE.with(|live| live.set(live.get() - 1));
+    }
+
+    pub(super) fn live() -> usize {
+        LIVE.with(Cell::get)
+    }
+}
+
+// without the feature the registry is gone, and so is its cost
+#[cfg(not(all(feature = "leak_check", debug_assertions)))]
+mod leak_check {
+    pub(super) fn register() {}
+    pub(super) fn unregister() {}
+}
+
+/// # Info
+/// Layout of the RcInner holding a value with this layout,
+/// the same one repr(C) gives: the counts, then the value, padded at the end.
+fn inner_layout(value: Layout) -> Layout {
+    Layout::new::<RcInner<()>>()
+        .extend(value)
+        .unwrap()
+        .0
+        .pad_to_align()
+}
+
+/// # Offset of the value inside RcInner
+/// repr(C) puts the counts first, and the value right after them,
+/// rounded up to the alignment of the value.
+///
+/// # Safety
+/// ptr has to point at a live value inside an RcInner.
+unsafe fn data_offset<T: ?Sized>(ptr: *const T) -> usize {
+    // SAFE because the caller guarantees the value is alive
+    let align = mem::align_of_val(unsafe { &*ptr });
+    Layout::new::<RcInner<()>>().size().next_multiple_of(align)
 }
 
 impl<T> MyRc<T> {
     pub fn new(value: T) -> Self {
-        let inner = Box::new(RcInner {
-            ref_count: MyCell::new(1),
-            value: value,
-        });
+        MyRc::new_in(value, Global)
+    }
+
+    /// # Info
+    /// Same as new, but gives back Err(AllocError) instead of aborting
+    /// when the allocator is out of memory.
+    ///
+    /// # Common Usage
+    /// Embedded or other OOM sensitive code that wants to recover
+    /// from a failed allocation, e.g by dropping caches and trying again.
+    pub fn try_new(value: T) -> Result<MyRc<T>, AllocError> {
+        MyRc::try_new_in(value, Global)
+    }
+
+    /// # Info
+    /// Puts the value in a new MyRc that is pinned right away.
+    ///
+    /// The value lives in the heap allocation and never moves until it's dropped,
+    /// clones only copy the pointer. The only ways to move it out or get a &mut
+    /// (get_mut, make_mut, try_unwrap) need the MyRc itself,
+    /// which Pin keeps to itself. Deref still works, Pin forwards it.
+    ///
+    /// # Common Usage
+    /// Self-referential values, e.g futures shared between several owners.
+    pub fn pin(value: T) -> Pin<MyRc<T>> {
+        // SAFE because of the reasons above,
+        // the value is never moved out of the allocation while it's pinned
+        unsafe { Pin::new_unchecked(MyRc::new(value)) }
+    }
+
+    /// # Info
+    /// Builds a value that holds a MyWeak to its own MyRc,
+    /// e.g a node that knows its own handle.
+    ///
+    /// The allocation is made first with a strong count of 0, so data_fn gets
+    /// a MyWeak that can't be upgraded yet (gives None) but can be cloned and stored.
+    /// Once the value is in place the strong count goes to 1.
+    pub fn new_cyclic<F: FnOnce(&MyWeak<T>) -> T>(data_fn: F) -> MyRc<T> {
+        // same allocation as new, only the value is left for later
+        let layout = Layout::new::<RcInner<T>>();
+        let inner = match Global.allocate(layout) {
+            Ok(mem) => mem.cast::<RcInner<T>>(),
+            Err(AllocError) => alloc::handle_alloc_error(layout),
+        };
+        leak_check::register();
+
+        // SAFE because the memory is ours, and sized and aligned for an RcInner<T>.
+        // The value is never read before it's written below
+        unsafe {
+            ptr::addr_of_mut!((*inner.as_ptr()).ref_count).write(MyCell::new(0));
+            ptr::addr_of_mut!((*inner.as_ptr()).weak_count).write(MyCell::new(1));
+        }
+
+        // if data_fn panics this weak frees the allocation,
+        // there is no value to drop yet since the strong count is 0
+        let weak = MyWeak {
+            inner,
+            layout: Layout::new::<RcInner<T>>(),
+            alloc: Global,
+        };
+        let data = data_fn(&weak);
+
+        unsafe {
+            ptr::addr_of_mut!((*inner.as_ptr()).value).write(ManuallyDrop::new(data));
+        }
+        // SAFE because the weak keeps the allocation alive
+        unsafe { Counts::new(inner) }.ref_count.set(1);
+
+        // the weak ref from the start becomes the one held by the strong ones
+        mem::forget(weak);
+        MyRc {
+            inner,
+            alloc: Global,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, A: RcAllocator> MyRc<T, A> {
+    /// # Info
+    /// Same as new, but the RcInner comes from alloc,
+    /// and goes back to it when the last MyRc/MyWeak is dropped.
+    pub fn new_in(value: T, alloc: A) -> MyRc<T, A> {
+        match MyRc::try_new_in(value, alloc) {
+            Ok(rc) => rc,
+            Err(AllocError) => alloc::handle_alloc_error(Layout::new::<RcInner<T>>()),
+        }
+    }
+
+    /// # Info
+    /// Same as new_in, but gives back Err(AllocError) instead of aborting
+    /// when the allocator is out of memory. The value is dropped in that case.
+    pub fn try_new_in(value: T, alloc: A) -> Result<MyRc<T, A>, AllocError> {
+        let inner = alloc
+            .allocate(Layout::new::<RcInner<T>>())?
+            .cast::<RcInner<T>>();
+        leak_check::register();
+
+        // SAFE because the memory is ours, and sized and aligned for an RcInner<T>
+        unsafe {
+            inner.as_ptr().write(RcInner {
+                ref_count: MyCell::new(1),
+                weak_count: MyCell::new(1),
+                value: ManuallyDrop::new(value),
+            });
+        }
+
+        Ok(MyRc {
+            inner,
+            alloc,
+            _marker: PhantomData,
+        })
+    }
+
+    /// # Info
+    /// Clone-on-write version of get_mut.
+    ///
+    /// If this MyRc is not the only pointer to the value (MyWeak included),
+    /// the value is cloned into a fresh allocation first, and this MyRc
+    /// gets pointed at it. The other pointers keep the old value untouched.
+    ///
+    /// The fresh allocation comes from a clone of our allocator.
+    pub fn make_mut(this: &mut MyRc<T, A>) -> &mut T
+    where
+        T: Clone,
+        A: Clone,
+    {
+        if MyRc::get_mut(this).is_none() {
+            *this = MyRc::new_in((**this).clone(), this.alloc.clone());
+        }
+
+        // SAFE because now we are the only pointer to the allocation
+        unsafe { &mut (*this.inner.as_ptr()).value }
+    }
+
+    /// # Info
+    /// Gives the value back if this is the only MyRc pointing to it,
+    /// otherwise gives the MyRc back in Err.
+    pub fn try_unwrap(this: MyRc<T, A>) -> Result<T, MyRc<T, A>> {
+        if MyRc::strong_count(&this) == 1 {
+            // SAFE because we are the last Rc, and we forget it below
+            // so its Drop doesn't drop the value a second time
+            let value = unsafe { ptr::read(MyRc::as_ptr(&this)) };
+            this.inner().ref_count.set(0);
+
+            // give back the weak ref held by the strong ones,
+            // frees the allocation if there are no MyWeak left.
+            // The allocator moves into the weak, which drops it afterwards
+            let weak = MyWeak {
+                inner: this.inner,
+                layout: Layout::new::<RcInner<T>>(),
+                // SAFE because this is forgotten right after, so it's read only once
+                alloc: unsafe { ptr::read(&this.alloc) },
+            };
+            mem::forget(this);
+            drop(weak);
+
+            Ok(value)
+        } else {
+            Err(this)
+        }
+    }
+
+    /// # Info
+    /// Owned value either way: moved out if this is the only MyRc,
+    /// cloned otherwise. The other MyRc keep the value they point to.
+    ///
+    /// MyWeak don't count, same as try_unwrap they just can't upgrade anymore.
+    pub fn unwrap_or_clone(this: MyRc<T, A>) -> T
+    where
+        T: Clone,
+    {
+        MyRc::try_unwrap(this).unwrap_or_else(|rc| (*rc).clone())
+    }
+}
+
+impl<T: ?Sized, A: RcAllocator> MyRc<T, A> {
+    /// # Info
+    /// Number of MyRc pointing to the same allocation.
+    ///
+    /// It's an associated function, not a method,
+    /// so it doesn't collide with methods on T reached through Deref.
+    pub fn strong_count(this: &MyRc<T, A>) -> usize {
+        this.inner().ref_count.get()
+    }
+
+    /// # Info
+    /// Number of MyWeak pointing to the same allocation.
+    pub fn weak_count(this: &MyRc<T, A>) -> usize {
+        // don't count the weak ref held by all the strong ones
+        this.inner().weak_count.get() - 1
+    }
+
+    /// # Info
+    /// Gives a mutable ref to the value, but only if this is the only MyRc
+    /// pointing to it. Otherwise mutating would be visible through the others.
+    pub fn get_mut(this: &mut MyRc<T, A>) -> Option<&mut T> {
+        // a MyWeak could be upgraded while the &mut is alive, so it has to be unique too
+        if MyRc::strong_count(this) == 1 && this.inner().weak_count.get() == 1 {
+            // SAFE because we are the only Rc, and &mut self
+            // guarantees nobody is using our ref at the same time
+            Some(unsafe { &mut (*this.inner.as_ptr()).value })
+        } else {
+            None
+        }
+    }
+
+    /// # Info
+    /// Checks if both MyRc point to the same allocation.
+    ///
+    /// Compares pointers, not values, so two separately created MyRc
+    /// with equal values are not ptr_eq.
+    pub fn ptr_eq(a: &MyRc<T, A>, b: &MyRc<T, A>) -> bool {
+        // only the address matters, not the slice length/vtable
+        ptr::addr_eq(a.inner.as_ptr(), b.inner.as_ptr())
+    }
+
+    /// # Info
+    /// Same as ==, but gives true right away when both point to the same
+    /// allocation, without comparing the values.
+    ///
+    /// # Required for T to be Eq
+    /// The shortcut assumes a value is always equal to itself,
+    /// which PartialEq doesn't promise (f64::NAN != f64::NAN).
+    /// std takes it inside Rc's == for T: Eq through specialization,
+    /// on stable it has to be a separate function.
+    ///
+    /// # Common Usage
+    /// Values that are expensive to compare and often shared, e.g interned strings.
+    pub fn eq_or_ptr_eq(a: &MyRc<T, A>, b: &MyRc<T, A>) -> bool
+    where
+        T: Eq,
+    {
+        MyRc::ptr_eq(a, b) || **a == **b
+    }
+
+    /// # Info
+    /// Hashes the address of the allocation instead of the value,
+    /// the hashing counterpart of ptr_eq.
+    pub fn ptr_hash<H: Hasher>(this: &MyRc<T, A>, state: &mut H) {
+        // thin pointer, so the slice length/vtable isn't hashed either
+        this.inner.as_ptr().cast::<()>().hash(state);
+    }
+
+    /// # Info
+    /// Creates a MyWeak pointing to the same allocation.
+    pub fn downgrade(this: &MyRc<T, A>) -> MyWeak<T, A>
+    where
+        A: Clone,
+    {
+        increment(this.inner().weak_count);
+        MyWeak {
+            inner: this.inner,
+            // the value is alive, so its size and alignment can still be read
+            layout: inner_layout(Layout::for_value(&**this)),
+            alloc: this.alloc.clone(),
+        }
+    }
 
+    /// # Info
+    /// Raw pointer to the value, without touching the strong count.
+    /// Read only cousin of into_raw, the MyRc stays usable.
+    pub fn as_ptr(this: &MyRc<T, A>) -> *const T {
+        // addr_of projects to the value field without creating a ref
+        unsafe { ptr::addr_of!((*this.inner.as_ptr()).value) as *const T }
+    }
+
+    /// Same counts only view MyWeak uses, so the only refs to the value
+    /// are the ones handed out by Deref/get_mut.
+    fn inner(&self) -> Counts<'_> {
+        // SAFE because inner struct is deallocated only when
+        // the last Rc goes away
+        unsafe { Counts::new(self.inner) }
+    }
+}
+
+/// # Raw pointers
+/// A raw pointer only carries the value, not the allocator,
+/// so turning MyRc into one and back is only there for the global allocator.
+impl<T: ?Sized> MyRc<T> {
+    /// # Info
+    /// Turns the MyRc into a raw pointer to the value, without touching the
+    /// strong count. The ref is leaked until it's turned back with from_raw.
+    ///
+    /// # Common Usage
+    /// Handing the pointer over FFI, or storing it in an untyped container.
+    pub fn into_raw(this: MyRc<T>) -> *const T {
+        let ptr = MyRc::as_ptr(&this);
+        mem::forget(this);
+        ptr
+    }
+
+    /// # Info
+    /// Turns a pointer from into_raw back into the MyRc it came from.
+    ///
+    /// The pointer points at the value, so we step back over the counts
+    /// to get to the start of RcInner.
+    ///
+    /// # Safety
+    /// ptr has to come from MyRc::into_raw, and each of those pointers
+    /// can only be turned back once (it owns one strong ref).
+    pub unsafe fn from_raw(ptr: *const T) -> MyRc<T> {
+        let offset = unsafe { data_offset(ptr) };
+        // byte_sub keeps the slice length/vtable of the pointer
+        let inner = unsafe { ptr.byte_sub(offset) } as *mut RcInner<T>;
         MyRc {
-            // SAFE because box cannot give us a nullptr
-            inner: unsafe { NonNull::new_unchecked(Box::into_raw(inner)) },
+            inner: unsafe { NonNull::new_unchecked(inner) },
+            alloc: Global,
             _marker: PhantomData,
         }
     }
+
+    /// # Info
+    /// Adds a strong ref through a pointer from into_raw, without making a MyRc.
+    ///
+    /// # Safety
+    /// ptr has to come from MyRc::into_raw, and the allocation
+    /// has to still be alive (at least one strong ref) when this is called.
+    ///
+    /// # Common Usage
+    /// FFI code that hands the same raw pointer to several owners.
+    pub unsafe fn increment_strong_count(ptr: *const T) {
+        // ManuallyDrop so the temporary MyRc doesn't give its ref back
+        let rc = ManuallyDrop::new(unsafe { MyRc::from_raw(ptr) });
+        increment(rc.inner().ref_count);
+    }
+
+    /// # Info
+    /// Gives back a strong ref through a pointer from into_raw,
+    /// dropping the value (and freeing the allocation) if it was the last one.
+    ///
+    /// # Safety
+    /// ptr has to come from MyRc::into_raw, and it has to own the strong ref
+    /// it gives back, same as passing it to from_raw and dropping the result.
+    pub unsafe fn decrement_strong_count(ptr: *const T) {
+        drop(unsafe { MyRc::from_raw(ptr) });
+    }
+}
+
+/// # Info
+/// Lets the compiler turn MyRc<T> into MyRc<U> when T: Unsize<U>,
+/// e.g MyRc<i32> into MyRc<dyn Any>.
+///
+/// RcInner<T> is ?Sized with the value as its last field,
+/// so NonNull<RcInner<T>> becomes a fat pointer carrying the vtable of the value.
+/// The counts sit in front of it, at the same offset for every T.
+#[cfg(feature = "nightly")]
+impl<T: ?Sized + Unsize<U>, U: ?Sized, A: RcAllocator> CoerceUnsized<MyRc<U, A>> for MyRc<T, A> {}
+
+#[cfg(feature = "nightly")]
+impl<T: ?Sized + Unsize<U>, U: ?Sized, A: RcAllocator> CoerceUnsized<MyWeak<U, A>>
+    for MyWeak<T, A>
+{
+}
+
+#[cfg(all(feature = "leak_check", debug_assertions))]
+impl MyRc<()> {
+    /// # Info
+    /// Number of MyRc allocations still alive on this thread.
+    ///
+    /// Call it at a point where everything should have been dropped,
+    /// anything above what you expect is a leak, usually a cycle of MyRc
+    /// that keeps itself alive after all the outside handles are gone.
+    ///
+    /// Only there with the leak_check feature in debug builds.
+    pub fn report_leaked() -> usize {
+        leak_check::live()
+    }
+}
+
+impl<A: RcAllocator> MyRc<dyn Any, A> {
+    /// # Info
+    /// Checks the type of the value at runtime,
+    /// and if it's a U gives back the same MyRc typed as MyRc<U>.
+    /// Otherwise the original MyRc comes back in Err.
+    ///
+    /// Nothing gets allocated or counted, the pointer just loses the vtable.
+    ///
+    /// # Common Usage
+    /// Containers of MyRc<dyn Any> holding values of different types.
+    pub fn downcast<U: Any>(self) -> Result<MyRc<U, A>, MyRc<dyn Any, A>> {
+        if !(*self).is::<U>() {
+            return Err(self);
+        }
+
+        // SAFE because we just checked the value is a U,
+        // and the strong ref and the allocator of self move into the new MyRc
+        let inner = self.inner.cast::<RcInner<U>>();
+        let alloc = unsafe { ptr::read(&self.alloc) };
+        mem::forget(self);
+        Ok(MyRc {
+            inner,
+            alloc,
+            _marker: PhantomData,
+        })
+    }
+
+    /// # Info
+    /// Peeks at the value as a U without giving up the MyRc,
+    /// None if it's some other type. The count is left alone.
+    ///
+    /// Associated fn so it doesn't shadow downcast_ref of dyn Any through Deref.
+    pub fn downcast_ref<U: Any>(this: &MyRc<dyn Any, A>) -> Option<&U> {
+        (**this).downcast_ref::<U>()
+    }
 }
 
-impl<T> Clone for MyRc<T> {
+impl<T> MyRc<[T]> {
+    fn slice_layout(len: usize) -> Layout {
+        inner_layout(Layout::array::<T>(len).unwrap())
+    }
+
+    /// Allocates an RcInner<[T]> for len elements with both counts set to 1,
+    /// the elements are left uninitialized.
+    fn allocate_for_slice(len: usize) -> NonNull<RcInner<[T]>> {
+        let layout = Self::slice_layout(len);
+        // SAFE because the layout is never zero sized, the counts are always there
+        let mem = unsafe { alloc::alloc(layout) };
+        if mem.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        leak_check::register();
+
+        // the slice pointer carries the length, and the cast keeps it
+        let inner = ptr::slice_from_raw_parts_mut(mem as *mut T, len) as *mut RcInner<[T]>;
+        // SAFE because the memory is ours and big enough for the counts
+        unsafe {
+            ptr::addr_of_mut!((*inner).ref_count).write(MyCell::new(1));
+            ptr::addr_of_mut!((*inner).weak_count).write(MyCell::new(1));
+            NonNull::new_unchecked(inner)
+        }
+    }
+
+    /// # Info
+    /// Collects an iterator of known length straight into a MyRc<[T]>,
+    /// with a single allocation and no Vec in between.
+    ///
+    /// # Panics
+    /// If the iterator gives fewer items than its len() said.
+    /// ExactSizeIterator is a safe trait, so a wrong len can't be trusted blindly,
+    /// extra items are just left in the iterator.
+    ///
+    /// # Common Usage
+    /// Collecting from slices, ranges or maps over them.
+    pub fn from_iter_exact<I: ExactSizeIterator<Item = T>>(iter: I) -> MyRc<[T]> {
+        let len = iter.len();
+        MyRc::from_iter_with_len(iter, len)
+    }
+
+    /// Fills a fresh allocation with len items from the iterator.
+    fn from_iter_with_len<I: Iterator<Item = T>>(mut iter: I, len: usize) -> MyRc<[T]> {
+        // drops the items written so far and frees the allocation
+        // if the iterator panics or runs out too early
+        struct Guard<T> {
+            mem: *mut u8,
+            layout: Layout,
+            elems: *mut T,
+            written: usize,
+        }
+
+        impl<T> Drop for Guard<T> {
+            fn drop(&mut self) {
+                unsafe {
+                    ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.elems, self.written));
+                    alloc::dealloc(self.mem, self.layout);
+                }
+                leak_check::unregister();
+            }
+        }
+
+        let inner = Self::allocate_for_slice(len);
+        let elems = unsafe { ptr::addr_of_mut!((*inner.as_ptr()).value) as *mut T };
+        let mut guard = Guard {
+            mem: inner.as_ptr() as *mut u8,
+            layout: Self::slice_layout(len),
+            elems,
+            written: 0,
+        };
+
+        for i in 0..len {
+            let item = iter
+                .next()
+                .expect("iterator gave fewer items than expected");
+            // SAFE because i < len, so we stay inside the allocation
+            unsafe { elems.add(i).write(item) };
+            guard.written += 1;
+        }
+
+        mem::forget(guard);
+        MyRc {
+            inner,
+            alloc: Global,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized, A: RcAllocator + Clone> Clone for MyRc<T, A> {
     fn clone(&self) -> Self {
-        let inner = unsafe { self.inner.as_ref() };
-        let current_refs = inner.ref_count.get();
-        inner.ref_count.set(current_refs + 1);
+        increment(self.inner().ref_count);
         MyRc {
             inner: self.inner,
+            alloc: self.alloc.clone(),
             _marker: PhantomData,
         }
     }
 }
 
-impl<T> Deref for MyRc<T> {
+impl<T: ?Sized, A: RcAllocator> Deref for MyRc<T, A> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
         //SAFE because inner struct is deallocated only when
-        //the last Rc goes away
-        &unsafe { self.inner.as_ref() }.value
+        //the last Rc goes away.
+        //The ref is made from the raw pointer to the value field,
+        //so it only covers the value and not the counts next to it
+        unsafe { &*MyRc::as_ptr(self) }
     }
 }
 
-impl<T> Drop for MyRc<T> {
+impl<T: ?Sized, A: RcAllocator> Drop for MyRc<T, A> {
     fn drop(&mut self) {
-        let inner = unsafe { self.inner.as_ref() };
-        let current_refs = inner.ref_count.get();
-        if current_refs == 1 {
-            drop(inner);
-            //SAFE because we are keeping the ref count
-            //and will drop the pointer when the last rc goes away
-            unsafe { Box::from_raw(self.inner.as_ptr()) };
-        } else {
-            inner.ref_count.set(current_refs - 1)
+        // the counts view doesn't cover the value,
+        // so it can't overlap with the &mut we drop the value through below
+        let current_refs = self.inner().ref_count.get();
+        self.inner().ref_count.set(current_refs - 1);
+        if current_refs != 1 {
+            return;
+        }
+
+        // read before the value is dropped, it's needed to free the allocation
+        let layout = inner_layout(Layout::for_value(&**self));
+
+        //SAFE because we were the last rc, so nobody can reach the value anymore.
+        //It's dropped here exactly once, freeing the allocation later
+        //doesn't drop it again because it's in ManuallyDrop
+        unsafe { ManuallyDrop::drop(&mut (*self.inner.as_ptr()).value) };
+
+        // give back the weak ref held by the strong ones,
+        // frees the allocation if there are no MyWeak left
+        // SAFE because the strong ones owned that weak ref
+        unsafe { release_weak(self.inner, layout, &self.alloc) };
+    }
+}
+
+/// # Info
+/// Weak version of MyRc, doesn't keep the value alive,
+/// only the allocation, so it can tell if the value is still there.
+///
+/// Has to be upgraded to a MyRc to get to the value.
+///
+/// # Common Usage
+/// Breaking reference cycles, e.g parent pointers in a tree,
+/// where strong pointers both ways would never get deallocated.
+///
+/// # Required to store the layout
+/// The last MyWeak frees the allocation after the value is gone,
+/// and for an unsized value (e.g MyWeak<[T]>) the size of the allocation
+/// can only be read off the value itself. Making a ref to a value that
+/// was already dropped is not allowed, so the layout is kept from when
+/// the value was still alive.
+pub struct MyWeak<T: ?Sized, A: RcAllocator = Global> {
+    inner: NonNull<RcInner<T>>,
+    layout: Layout,
+    alloc: A,
+}
+
+/// Address of a MyWeak made by MyWeak::new, that has no allocation.
+///
+/// An RcInner is at least aligned to usize, so no real one can ever start there.
+const DANGLING: usize = usize::MAX;
+
+impl<T> MyWeak<T> {
+    /// # Info
+    /// Makes a MyWeak that doesn't point to anything, upgrade always gives None.
+    ///
+    /// Doesn't allocate, the pointer is a sentinel address
+    /// that the other methods check for before touching the counts.
+    ///
+    /// # Common Usage
+    /// Placeholder for a parent that doesn't exist yet when building a tree.
+    pub fn new() -> MyWeak<T> {
+        MyWeak {
+            // SAFE because DANGLING is not 0
+            inner: unsafe {
+                NonNull::new_unchecked(ptr::without_provenance_mut::<RcInner<T>>(DANGLING))
+            },
+            layout: Layout::new::<RcInner<T>>(),
+            alloc: Global,
+        }
+    }
+}
+
+impl<T> Default for MyWeak<T> {
+    fn default() -> Self {
+        MyWeak::new()
+    }
+}
+
+impl<T: ?Sized, A: RcAllocator> MyWeak<T, A> {
+    /// # Info
+    /// Gives a MyRc if the value is still alive, None otherwise.
+    pub fn upgrade(&self) -> Option<MyRc<T, A>>
+    where
+        A: Clone,
+    {
+        let inner = self.inner()?;
+        if inner.ref_count.get() == 0 {
+            return None;
+        }
+
+        increment(inner.ref_count);
+        Some(MyRc {
+            inner: self.inner,
+            alloc: self.alloc.clone(),
+            _marker: PhantomData,
+        })
+    }
+
+    /// # Info
+    /// Number of MyRc pointing to the value, 0 once it's gone.
+    ///
+    /// Methods and not associated fns like on MyRc,
+    /// MyWeak has no Deref so nothing can be shadowed.
+    pub fn strong_count(&self) -> usize {
+        self.inner().map_or(0, |inner| inner.ref_count.get())
+    }
+
+    /// # Info
+    /// Number of MyWeak pointing to the allocation, this one included.
+    ///
+    /// 0 once the value is gone, same as std Weak,
+    /// and always 0 for a MyWeak from MyWeak::new.
+    pub fn weak_count(&self) -> usize {
+        match self.inner() {
+            // don't count the weak ref held by all the strong ones
+            Some(inner) if inner.ref_count.get() > 0 => inner.weak_count.get() - 1,
+            _ => 0,
+        }
+    }
+
+    /// None for a MyWeak from MyWeak::new, there are no counts to look at.
+    fn inner(&self) -> Option<Counts<'_>> {
+        // address only, so a dangling MyWeak<dyn Trait> is caught too
+        if self.inner.as_ptr().cast::<()>().addr() == DANGLING {
+            return None;
+        }
+
+        // SAFE because the allocation is freed only when
+        // the last weak ref goes away
+        Some(unsafe { Counts::new(self.inner) })
+    }
+}
+
+/// # Info
+/// Refs to just the counts of an RcInner.
+///
+/// # Required to leave the value out
+/// A ref to the whole RcInner would also cover the value, which might be
+/// mutably borrowed or in the middle of being dropped
+/// (e.g the value holds a MyWeak to itself and it's dropping it).
+/// Under Stacked Borrows that overlap is UB, so the counts are
+/// projected out of the raw pointer one by one instead.
+struct Counts<'a> {
+    ref_count: &'a MyCell<usize>,
+    weak_count: &'a MyCell<usize>,
+}
+
+impl<'a> Counts<'a> {
+    /// SAFE only while the allocation is alive, for as long as 'a.
+    unsafe fn new<T: ?Sized>(inner: NonNull<RcInner<T>>) -> Counts<'a> {
+        let inner = inner.as_ptr();
+        unsafe {
+            Counts {
+                ref_count: &(*inner).ref_count,
+                weak_count: &(*inner).weak_count,
+            }
+        }
+    }
+}
+
+impl<T: ?Sized, A: RcAllocator + Clone> Clone for MyWeak<T, A> {
+    fn clone(&self) -> Self {
+        if let Some(inner) = self.inner() {
+            increment(inner.weak_count);
+        }
+        MyWeak {
+            inner: self.inner,
+            layout: self.layout,
+            alloc: self.alloc.clone(),
+        }
+    }
+}
+
+impl<T: ?Sized, A: RcAllocator> Drop for MyWeak<T, A> {
+    fn drop(&mut self) {
+        if self.inner().is_none() {
+            return;
+        }
+
+        // SAFE because this MyWeak owns the weak ref it gives back
+        unsafe { release_weak(self.inner, self.layout, &self.alloc) };
+    }
+}
+
+impl<T> From<Vec<T>> for MyRc<[T]> {
+    fn from(mut v: Vec<T>) -> Self {
+        let len = v.len();
+        let inner = MyRc::<[T]>::allocate_for_slice(len);
+        // SAFE because the allocation has room for exactly len elements
+        unsafe {
+            let elems = ptr::addr_of_mut!((*inner.as_ptr()).value) as *mut T;
+            ptr::copy_nonoverlapping(v.as_ptr(), elems, len);
+            // the elements are moved into the rc now,
+            // the vec should only free its buffer
+            v.set_len(0);
+        }
+
+        MyRc {
+            inner,
+            alloc: Global,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Clone> From<&[T]> for MyRc<[T]> {
+    fn from(slice: &[T]) -> Self {
+        MyRc::from_iter_exact(slice.iter().cloned())
+    }
+}
+
+/// # Info
+/// Collects into a Vec first and moves the elements over from there,
+/// since the size hint of an iterator can't be trusted for the allocation.
+impl<T> FromIterator<T> for MyRc<[T]> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        MyRc::from(iter.into_iter().collect::<Vec<T>>())
+    }
+}
+
+/// # Info
+/// `{:?}` prints just the value, like Rc does.
+/// `{:#?}` also prints the strong and weak counts,
+/// handy when hunting down what keeps a value alive.
+impl<T: ?Sized + fmt::Debug, A: RcAllocator> fmt::Debug for MyRc<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !f.alternate() {
+            return fmt::Debug::fmt(&**self, f);
+        }
+
+        f.debug_struct("MyRc")
+            .field("value", &&**self)
+            .field("strong", &MyRc::strong_count(self))
+            .field("weak", &MyRc::weak_count(self))
+            .finish()
+    }
+}
+
+/// # Info
+/// Prints `(Weak)` once the value is gone (or for MyWeak::new),
+/// and the value while it's still alive.
+///
+/// Looking at the value needs a short lived upgrade,
+/// so the strong count is one higher while T's Debug runs.
+impl<T: ?Sized + fmt::Debug, A: RcAllocator + Clone> fmt::Debug for MyWeak<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.upgrade() {
+            Some(rc) => f.debug_tuple("MyWeak").field(&&*rc).finish(),
+            None => f.write_str("(Weak)"),
+        }
+    }
+}
+
+impl<T: ?Sized + fmt::Display, A: RcAllocator> fmt::Display for MyRc<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// # Info
+/// Compares the values, not the pointers, use MyRc::ptr_eq for that.
+///
+/// There is no shortcut for two MyRc pointing to the same allocation here,
+/// for plain PartialEq a value doesn't have to be equal to itself (f64::NAN).
+/// MyRc::eq_or_ptr_eq takes it for T: Eq.
+impl<T: ?Sized + PartialEq, A: RcAllocator> PartialEq for MyRc<T, A> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: ?Sized + Eq, A: RcAllocator> Eq for MyRc<T, A> {}
+
+/// # Info
+/// Orders by value, same as the comparisons above.
+/// Where the allocations live doesn't matter, unlike with MyRc::ptr_eq.
+impl<T: ?Sized + PartialOrd, A: RcAllocator> PartialOrd for MyRc<T, A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: ?Sized + Ord, A: RcAllocator> Ord for MyRc<T, A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+/// # Info
+/// Hashes the value, so MyRc<T> can be used as a key in place of T.
+impl<T: ?Sized + Hash, A: RcAllocator> Hash for MyRc<T, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+/// # Info
+/// Wraps a pointer so it compares and hashes by identity, not by value.
+///
+/// Two MyRc with equal values but different allocations are different keys,
+/// while clones of the same MyRc are the same key.
+///
+/// # Common Usage
+/// Graphs keyed by node, e.g a HashSet<ByPtr<MyRc<Node>>> of visited nodes,
+/// where Node might not even impl Hash/Eq.
+#[derive(Clone, Debug)]
+pub struct ByPtr<P>(pub P);
+
+impl<T: ?Sized, A: RcAllocator> PartialEq for ByPtr<MyRc<T, A>> {
+    fn eq(&self, other: &Self) -> bool {
+        MyRc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T: ?Sized, A: RcAllocator> Eq for ByPtr<MyRc<T, A>> {}
+
+impl<T: ?Sized, A: RcAllocator> Hash for ByPtr<MyRc<T, A>> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        MyRc::ptr_hash(&self.0, state);
+    }
+}
+
+impl<T: ?Sized, A: RcAllocator> AsRef<T> for MyRc<T, A> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+/// # Info
+/// Lets maps keyed by MyRc<T> be looked up with a &T,
+/// which works because Hash/Eq/Ord above all go by the value.
+impl<T: ?Sized, A: RcAllocator> Borrow<T> for MyRc<T, A> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T: Default> Default for MyRc<T> {
+    fn default() -> Self {
+        MyRc::new(T::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::cell::Cell;
+    use std::{
+        collections::{BTreeSet, HashMap},
+        format,
+        panic::{self, AssertUnwindSafe},
+        string::String,
+        vec,
+        vec::Vec,
+    };
+
+    /// Counts its drops, to catch a value dropped twice or never.
+    struct DropCount<'a>(&'a Cell<usize>);
+
+    impl Drop for DropCount<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    /// Global allocator that counts what goes through it,
+    /// handed to MyRc::new_in by ref.
+    #[derive(Default)]
+    struct CountingAlloc {
+        allocs: Cell<usize>,
+        bytes: Cell<usize>,
+        frees: Cell<usize>,
+    }
+
+    unsafe impl RcAllocator for &CountingAlloc {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+            self.allocs.set(self.allocs.get() + 1);
+            self.bytes.set(self.bytes.get() + layout.size());
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.frees.set(self.frees.get() + 1);
+            unsafe { Global.deallocate(ptr, layout) }
+        }
+    }
+
+    #[test]
+    fn weak_keeps_allocation_after_value_drops() {
+        let alloc = CountingAlloc::default();
+        let drops = Cell::new(0);
+        let rc = MyRc::new_in(DropCount(&drops), &alloc);
+        let clone = rc.clone();
+        let weak = MyRc::downgrade(&rc);
+
+        drop(rc);
+        drop(clone);
+        // the value is gone, but the counts are still there for the weak
+        assert_eq!(drops.get(), 1);
+        assert_eq!(alloc.frees.get(), 0);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(weak.weak_count(), 0);
+
+        drop(weak);
+        assert_eq!(drops.get(), 1);
+        assert_eq!(alloc.allocs.get(), 1);
+        assert_eq!(alloc.frees.get(), 1);
+    }
+
+    #[test]
+    fn allocations_and_frees_balance() {
+        let alloc = CountingAlloc::default();
+
+        let rc = MyRc::new_in(String::from("hi"), &alloc);
+        let weak = MyRc::downgrade(&rc);
+        let clone = rc.clone();
+        // clones and weaks share the one allocation
+        assert_eq!(alloc.allocs.get(), 1);
+        drop(rc);
+        drop(clone);
+        drop(weak);
+        assert_eq!(alloc.frees.get(), 1);
+
+        let rc = MyRc::new_in(5u8, &alloc);
+        assert_eq!(MyRc::try_unwrap(rc).ok(), Some(5));
+        assert_eq!(alloc.frees.get(), 2);
+
+        // a failed try_unwrap hands the rc back, nothing is freed
+        let rc = MyRc::new_in(vec![1], &alloc);
+        let clone = rc.clone();
+        let rc = MyRc::try_unwrap(rc).unwrap_err();
+        assert_eq!(alloc.frees.get(), 2);
+
+        // unwrapping with a weak around moves the value out, the weak keeps the header
+        drop(clone);
+        let weak = MyRc::downgrade(&rc);
+        assert_eq!(MyRc::try_unwrap(rc).ok(), Some(vec![1]));
+        assert_eq!(alloc.frees.get(), 2);
+        drop(weak);
+
+        assert_eq!(alloc.allocs.get(), 3);
+        assert_eq!(alloc.allocs.get(), alloc.frees.get());
+    }
+
+    #[test]
+    fn strong_count_overflow_never_wraps() {
+        let drops = Cell::new(0);
+        let rc = MyRc::new(DropCount(&drops));
+        let ptr = MyRc::as_ptr(&rc);
+        for _ in 1..MAX_COUNT {
+            // SAFE because rc keeps the value alive
+            unsafe { MyRc::increment_strong_count(ptr) };
+        }
+        assert_eq!(MyRc::strong_count(&rc), MAX_COUNT);
+
+        let clone = panic::catch_unwind(AssertUnwindSafe(|| rc.clone()));
+        assert!(clone.is_err());
+        assert_eq!(MyRc::strong_count(&rc), MAX_COUNT);
+
+        for _ in 1..MAX_COUNT {
+            // SAFE because every one of these was incremented above
+            unsafe { MyRc::decrement_strong_count(ptr) };
+        }
+        drop(rc);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn weak_count_overflow_never_wraps() {
+        let rc = MyRc::new(5);
+        // the rcs already hold one weak together
+        for _ in 1..MAX_COUNT {
+            mem::forget(MyRc::downgrade(&rc));
+        }
+        assert_eq!(MyRc::weak_count(&rc), MAX_COUNT - 1);
+
+        let weak = panic::catch_unwind(AssertUnwindSafe(|| MyRc::downgrade(&rc)));
+        assert!(weak.is_err());
+        assert_eq!(MyRc::weak_count(&rc), MAX_COUNT - 1);
+
+        // forget the forgotten weaks, so the last rc frees the allocation
+        rc.inner().weak_count.set(1);
+        assert_eq!(MyRc::try_unwrap(rc).ok(), Some(5));
+    }
+
+    #[test]
+    fn string_keys_in_a_hash_map() {
+        let mut map: HashMap<MyRc<String>, i32> = HashMap::new();
+        let key = MyRc::new(String::from("one"));
+        map.insert(key.clone(), 1);
+        map.insert(MyRc::new(String::from("two")), 2);
+
+        // clones, equal values in other allocations and plain Strings all find the entry
+        assert_eq!(map.get(&key), Some(&1));
+        assert_eq!(map.get(&MyRc::new(String::from("one"))), Some(&1));
+        assert_eq!(map.get(&String::from("two")), Some(&2));
+        assert_eq!(map.get(&MyRc::new(String::from("three"))), None);
+    }
+
+    #[test]
+    fn eq_or_ptr_eq_skips_the_values_for_one_allocation() {
+        // counts how often the values really get compared
+        struct Counted<'a>(i32, &'a Cell<usize>);
+
+        impl PartialEq for Counted<'_> {
+            fn eq(&self, other: &Self) -> bool {
+                self.1.set(self.1.get() + 1);
+                self.0 == other.0
+            }
+        }
+
+        impl Eq for Counted<'_> {}
+
+        let compared = Cell::new(0);
+        let a = MyRc::new(Counted(1, &compared));
+        assert!(MyRc::eq_or_ptr_eq(&a, &a.clone()));
+        assert_eq!(compared.get(), 0);
+
+        // different allocations still go by value
+        assert!(MyRc::eq_or_ptr_eq(&a, &MyRc::new(Counted(1, &compared))));
+        assert!(!MyRc::eq_or_ptr_eq(&a, &MyRc::new(Counted(2, &compared))));
+        assert_eq!(compared.get(), 2);
+
+        // == always compares
+        assert!(a == a.clone());
+        assert_eq!(compared.get(), 3);
+    }
+
+    #[test]
+    fn zero_sized_values_allocate_only_the_counts() {
+        let alloc = CountingAlloc::default();
+        let rcs: Vec<_> = (0..100).map(|_| MyRc::new_in((), &alloc)).collect();
+        // one allocation each, with nothing in it for the value
+        assert_eq!(alloc.allocs.get(), 100);
+        assert_eq!(alloc.bytes.get(), 100 * 2 * mem::size_of::<usize>());
+
+        drop(rcs);
+        assert_eq!(alloc.frees.get(), 100);
+    }
+
+    #[test]
+    fn zero_sized_values_keep_their_own_counts() {
+        let first = MyRc::new(());
+        let clones: Vec<_> = (0..100).map(|_| first.clone()).collect();
+        assert_eq!(MyRc::strong_count(&first), 101);
+        drop(clones);
+        assert_eq!(MyRc::strong_count(&first), 1);
+
+        // two unrelated ones are each unique
+        let mut a = MyRc::new(());
+        let mut b = MyRc::new(());
+        assert!(!MyRc::ptr_eq(&a, &b));
+        assert_eq!(MyRc::strong_count(&a), 1);
+        assert!(MyRc::get_mut(&mut a).is_some());
+        assert!(MyRc::get_mut(&mut b).is_some());
+        let before = MyRc::as_ptr(&a);
+        MyRc::make_mut(&mut a);
+        assert_eq!(MyRc::as_ptr(&a), before);
+        assert_eq!(MyRc::try_unwrap(b).ok(), Some(()));
+
+        // a weak doesn't come back to life through a later one
+        let weak = MyRc::downgrade(&first);
+        drop(first);
+        let _later = MyRc::new(());
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn strong_count_follows_clones() {
+        let rc = MyRc::new(1);
+        assert_eq!(MyRc::strong_count(&rc), 1);
+        let a = rc.clone();
+        let b = rc.clone();
+        assert_eq!(MyRc::strong_count(&rc), 3);
+        drop(a);
+        assert_eq!(MyRc::strong_count(&b), 2);
+        drop(b);
+        assert_eq!(MyRc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    fn get_mut_only_when_unique() {
+        let mut rc = MyRc::new(1);
+        *MyRc::get_mut(&mut rc).unwrap() += 1;
+        let clone = rc.clone();
+        assert!(MyRc::get_mut(&mut rc).is_none());
+        drop(clone);
+
+        // a weak could upgrade while the &mut is alive, so it blocks get_mut too
+        let weak = MyRc::downgrade(&rc);
+        assert!(MyRc::get_mut(&mut rc).is_none());
+        drop(weak);
+        assert_eq!(MyRc::get_mut(&mut rc), Some(&mut 2));
+    }
+
+    #[test]
+    fn try_unwrap_unique_and_shared() {
+        let rc = MyRc::new(String::from("a"));
+        assert_eq!(MyRc::try_unwrap(rc).ok().as_deref(), Some("a"));
+
+        let rc = MyRc::new(String::from("b"));
+        let clone = rc.clone();
+        let rc = MyRc::try_unwrap(rc).unwrap_err();
+        assert_eq!(MyRc::strong_count(&rc), 2);
+        drop(clone);
+        assert_eq!(MyRc::try_unwrap(rc).ok().as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn ptr_eq_and_as_ptr() {
+        let rc = MyRc::new(1);
+        let clone = rc.clone();
+        assert!(MyRc::ptr_eq(&rc, &clone));
+        assert_eq!(MyRc::as_ptr(&rc), MyRc::as_ptr(&clone));
+        assert!(!MyRc::ptr_eq(&rc, &MyRc::new(1)));
+    }
+
+    #[test]
+    fn weak_upgrade_before_and_after_drop() {
+        let rc = MyRc::new(5);
+        let weak = MyRc::downgrade(&rc);
+        let other = weak.clone();
+        assert_eq!(MyRc::weak_count(&rc), 2);
+        assert_eq!(weak.upgrade().as_deref(), Some(&5));
+        drop(other);
+        assert_eq!(MyRc::weak_count(&rc), 1);
+
+        drop(rc);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn weak_counts_through_the_weak() {
+        let rc = MyRc::new(1);
+        let clone = rc.clone();
+        let weak = MyRc::downgrade(&rc);
+        let weak2 = weak.clone();
+        assert_eq!(weak.strong_count(), 2);
+        assert_eq!(weak.weak_count(), 2);
+
+        drop((rc, clone));
+        // std gives 0 for both once the value is gone
+        assert_eq!(weak.strong_count(), 0);
+        assert_eq!(weak2.weak_count(), 0);
+    }
+
+    #[test]
+    fn make_mut_unique_and_shared() {
+        let mut rc = MyRc::new(vec![1]);
+        MyRc::make_mut(&mut rc).push(2);
+        assert_eq!(MyRc::strong_count(&rc), 1);
+
+        let original = rc.clone();
+        MyRc::make_mut(&mut rc).push(3);
+        assert!(!MyRc::ptr_eq(&rc, &original));
+        assert_eq!(*original, [1, 2]);
+        assert_eq!(*rc, [1, 2, 3]);
+        assert_eq!(MyRc::strong_count(&original), 1);
+    }
+
+    #[test]
+    fn value_drops_exactly_once() {
+        let drops = Cell::new(0);
+        let rc = MyRc::new(DropCount(&drops));
+        let clones: Vec<_> = (0..3).map(|_| rc.clone()).collect();
+        drop(rc);
+        assert_eq!(drops.get(), 0);
+        drop(clones);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn slices_in_one_allocation() {
+        let bytes: MyRc<[u8]> = MyRc::from(vec![1u8, 2, 3]);
+        let clone = bytes.clone();
+        drop(bytes);
+        assert_eq!(*clone, [1, 2, 3]);
+        assert_eq!(MyRc::<[u8]>::from(&[4u8][..])[0], 4);
+
+        let collected: MyRc<[i32]> = (0..5).collect();
+        assert_eq!(collected.len(), 5);
+        assert_eq!(*collected, [0, 1, 2, 3, 4]);
+
+        let exact = MyRc::from_iter_exact(0..1000);
+        assert_eq!(exact.len(), 1000);
+        assert!(exact.iter().copied().eq(0..1000));
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_iter_exact_with_a_lying_len_panics() {
+        struct Lying(core::ops::Range<i32>);
+        impl Iterator for Lying {
+            type Item = i32;
+            fn next(&mut self) -> Option<i32> {
+                self.0.next()
+            }
+        }
+        impl ExactSizeIterator for Lying {
+            fn len(&self) -> usize {
+                10
+            }
+        }
+
+        let _ = MyRc::from_iter_exact(Lying(0..3));
+    }
+
+    #[test]
+    fn debug_and_display() {
+        let rc = MyRc::new(String::from("hi"));
+        assert_eq!(format!("{}", rc), format!("{}", *rc));
+        assert_eq!(format!("{:?}", rc), format!("{:?}", *rc));
+
+        let weak = MyRc::downgrade(&rc);
+        assert_eq!(
+            format!("{:#?}", rc),
+            "MyRc {\n    value: \"hi\",\n    strong: 1,\n    weak: 1,\n}"
+        );
+        assert_eq!(format!("{:?}", weak), "MyWeak(\"hi\")");
+        drop(rc);
+        assert_eq!(format!("{:?}", weak), "(Weak)");
+        assert_eq!(format!("{:?}", MyWeak::<i32>::new()), "(Weak)");
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        let rc = MyRc::new(String::from("raw"));
+        let clone = rc.clone();
+        let ptr = MyRc::into_raw(rc);
+        // SAFE because ptr came from into_raw and is given back once
+        let rc = unsafe { MyRc::from_raw(ptr) };
+        assert_eq!(*rc, "raw");
+        assert_eq!(MyRc::strong_count(&clone), 2);
+    }
+
+    #[test]
+    fn raw_count_statics() {
+        let drops = Cell::new(0);
+        let ptr = MyRc::into_raw(MyRc::new(DropCount(&drops)));
+        // SAFE because every increment is matched by a decrement below,
+        // and the last decrement gives back the ref into_raw kept
+        unsafe {
+            MyRc::increment_strong_count(ptr);
+            MyRc::decrement_strong_count(ptr);
+            assert_eq!(drops.get(), 0);
+            MyRc::decrement_strong_count(ptr);
+        }
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn ordered_by_value() {
+        let mut rcs: Vec<_> = [3, 1, 2].into_iter().map(MyRc::new).collect();
+        rcs.sort();
+        assert!(rcs.iter().map(|rc| **rc).eq([1, 2, 3]));
+
+        let set: BTreeSet<_> = [2, 1, 2].into_iter().map(MyRc::new).collect();
+        assert!(set.iter().map(|rc| **rc).eq([1, 2]));
+    }
+
+    #[test]
+    fn new_cyclic_upgrades_after_construction() {
+        struct Node {
+            me: MyWeak<Node>,
+            value: i32,
+        }
+
+        let rc = MyRc::new_cyclic(|me| {
+            // nothing to upgrade to yet
+            assert!(me.upgrade().is_none());
+            Node {
+                me: me.clone(),
+                value: 7,
+            }
+        });
+        assert_eq!(rc.me.upgrade().map(|node| node.value), Some(7));
+        assert!(MyRc::ptr_eq(&rc.me.upgrade().unwrap(), &rc));
+    }
+
+    #[test]
+    fn as_ref_borrow_and_default() {
+        let rc = MyRc::new(String::from("k"));
+        let s: &String = rc.as_ref();
+        assert_eq!(s, "k");
+
+        let mut map: HashMap<MyRc<String>, i32> = HashMap::new();
+        map.insert(rc, 1);
+        assert_eq!(map.get(&String::from("k")), Some(&1));
+
+        #[derive(Default)]
+        struct Buf {
+            bytes: MyRc<Vec<u8>>,
+        }
+        assert!(Buf::default().bytes.is_empty());
+    }
+
+    #[test]
+    fn downcast_right_and_wrong_type() {
+        // the stable way in, the raw pointer coerces even without the nightly feature
+        let ptr = MyRc::into_raw(MyRc::new(String::from("s"))) as *const dyn Any;
+        // SAFE because ptr came from into_raw, it only gained a vtable
+        let any = unsafe { MyRc::from_raw(ptr) };
+        assert_eq!(
+            MyRc::downcast_ref::<String>(&any).map(String::as_str),
+            Some("s")
+        );
+        assert!(MyRc::downcast_ref::<i32>(&any).is_none());
+
+        let any = any.downcast::<i32>().unwrap_err();
+        assert_eq!(*any.downcast::<String>().unwrap(), "s");
+    }
+
+    #[test]
+    fn pin_derefs_to_the_value() {
+        let pinned = MyRc::pin(5);
+        assert_eq!(*pinned, 5);
+        let clone = pinned.clone();
+        assert_eq!(*clone + *pinned, 10);
+    }
+
+    #[test]
+    fn by_ptr_keys_by_identity() {
+        let a = MyRc::new(1);
+        let mut map = HashMap::new();
+        map.insert(ByPtr(a.clone()), "a");
+        map.insert(ByPtr(MyRc::new(1)), "other");
+        map.insert(ByPtr(a.clone()), "a again");
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&ByPtr(a)), Some(&"a again"));
+    }
+
+    #[test]
+    fn dangling_weak_replaced_by_a_real_one() {
+        struct Child {
+            parent: MyWeak<i32>,
+        }
+
+        let mut child = Child {
+            parent: MyWeak::new(),
+        };
+        assert!(child.parent.upgrade().is_none());
+        assert_eq!(child.parent.strong_count(), 0);
+
+        let parent = MyRc::new(1);
+        child.parent = MyRc::downgrade(&parent);
+        assert_eq!(child.parent.upgrade().as_deref(), Some(&1));
+    }
+
+    #[test]
+    fn try_new_succeeds() {
+        // running out of memory would come back as Err(AllocError)
+        let rc = MyRc::try_new(3).unwrap();
+        assert_eq!(*rc, 3);
+    }
+
+    #[test]
+    fn unwrap_or_clone_unique_and_shared() {
+        let rc = MyRc::new(String::from("a"));
+        let ptr = rc.as_ptr();
+        let moved = MyRc::unwrap_or_clone(rc);
+        // moved out, the String buffer is the same one
+        assert_eq!(moved.as_ptr(), ptr);
+
+        let rc = MyRc::new(String::from("b"));
+        let original = rc.clone();
+        let mut cloned = MyRc::unwrap_or_clone(rc);
+        cloned.push('!');
+        assert_eq!(*original, "b");
+        assert_eq!(MyRc::strong_count(&original), 1);
+    }
+
+    #[test]
+    fn clone_drop_interleavings() {
+        // mixes clones, weaks and drops in an odd order, mostly for Miri
+        let drops = Cell::new(0);
+        let rc = MyRc::new(DropCount(&drops));
+        let mut strong = vec![rc.clone()];
+        let mut weak = vec![MyRc::downgrade(&rc)];
+        for i in 0..if cfg!(miri) { 20 } else { 200 } {
+            match i % 4 {
+                0 => strong.push(strong[0].clone()),
+                1 => weak.push(weak.last().unwrap().clone()),
+                2 => drop(strong.pop()),
+                _ => {
+                    if let Some(up) = weak[0].upgrade() {
+                        strong.push(up);
+                    }
+                }
+            }
+        }
+        drop(rc);
+        drop(strong);
+        assert_eq!(drops.get(), 1);
+        assert!(weak.iter().all(|w| w.upgrade().is_none()));
+    }
+
+    #[test]
+    fn weak_to_a_slice_outlives_the_values() {
+        // the last weak frees an unsized allocation after its values are gone,
+        // Miri checks the layout it frees with matches the one allocated
+        let drops = Cell::new(0);
+        let rc: MyRc<[DropCount]> = MyRc::from(vec![DropCount(&drops), DropCount(&drops)]);
+        let weak = MyRc::downgrade(&rc);
+        let weak_clone = weak.clone();
+
+        drop(rc);
+        assert_eq!(drops.get(), 2);
+        assert!(weak.upgrade().is_none());
+        drop(weak);
+        drop(weak_clone);
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[cfg(feature = "nightly")]
+    #[test]
+    fn trait_objects_through_unsizing() {
+        let k = 10;
+        let fns: Vec<MyRc<dyn Fn() -> i32>> =
+            vec![MyRc::new(|| 1), MyRc::new(move || k), MyRc::new(|| 3)];
+        assert_eq!(fns.iter().map(|f| f()).sum::<i32>(), 14);
+    }
+
+    #[cfg(all(feature = "leak_check", debug_assertions))]
+    #[test]
+    fn new_cyclic_is_counted_like_new() {
+        struct Node {
+            me: MyWeak<Node>,
+        }
+
+        let base = MyRc::report_leaked();
+        let node = MyRc::new_cyclic(|me| Node { me: me.clone() });
+        assert!(MyRc::ptr_eq(&node.me.upgrade().unwrap(), &node));
+        assert_eq!(MyRc::report_leaked(), base + 1);
+
+        drop(node);
+        assert_eq!(MyRc::report_leaked(), base);
+    }
+
+    #[cfg(all(feature = "leak_check", debug_assertions))]
+    #[cfg_attr(miri, ignore = "leaks on purpose")]
+    #[test]
+    fn report_leaked_finds_cycles() {
+        use crate::refcell::MyRefCell;
+
+        struct Node {
+            other: MyRefCell<Option<MyRc<Node>>>,
+        }
+
+        let base = MyRc::report_leaked();
+        {
+            let a = MyRc::new(Node {
+                other: MyRefCell::new(None),
+            });
+            let _b = MyRc::new(Node {
+                other: MyRefCell::new(Some(a.clone())),
+            });
+        }
+        assert_eq!(MyRc::report_leaked(), base);
+
+        {
+            let a = MyRc::new(Node {
+                other: MyRefCell::new(None),
+            });
+            let b = MyRc::new(Node {
+                other: MyRefCell::new(Some(a.clone())),
+            });
+            *a.other.borrow_mut() = Some(b);
         }
+        assert_eq!(MyRc::report_leaked(), base + 2);
     }
 }
@@ -0,0 +1,172 @@
+//! # Shared mutable pointer, MyRc<MyRefCell<T>> in one type
+//! With most essential info about it
+
+use core::{fmt, ops::Deref};
+
+use crate::{
+    rc::MyRc,
+    refcell::{MyRefCell, Ref, RefMut},
+};
+
+/// # Info
+/// Thin wrapper over MyRc<MyRefCell<T>>, the go-to combination for
+/// data that has many owners and needs to be mutated.
+///
+/// MyRc gives the many owners, MyRefCell gives the mutation through a shared ref.
+/// The wrapper just saves spelling out the nesting, borrow goes straight
+/// to the refcell and clone to the rc.
+///
+/// Everything else on MyRefCell (try_borrow, replace, ...) is reached through Deref.
+///
+/// # Required to be repr(transparent)
+/// So it's really zero cost, laid out exactly like the MyRc inside.
+///
+/// # Common Usage
+/// Graph or linked list nodes that are shared and mutated from several places.
+#[repr(transparent)]
+pub struct MyRcCell<T> {
+    rc: MyRc<MyRefCell<T>>,
+}
+
+impl<T> MyRcCell<T> {
+    pub fn new(value: T) -> Self {
+        MyRcCell {
+            rc: MyRc::new(MyRefCell::new(value)),
+        }
+    }
+
+    /// # Panics
+    /// If the value is currently mutably borrowed.
+    #[track_caller]
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.rc.borrow()
+    }
+
+    /// # Panics
+    /// If the value is currently borrowed, shared or mutably.
+    #[track_caller]
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.rc.borrow_mut()
+    }
+
+    /// Number of MyRcCell pointing to the same value.
+    pub fn strong_count(this: &MyRcCell<T>) -> usize {
+        MyRc::strong_count(&this.rc)
+    }
+
+    /// Checks if both point to the same value, not if the values are equal.
+    pub fn ptr_eq(a: &MyRcCell<T>, b: &MyRcCell<T>) -> bool {
+        MyRc::ptr_eq(&a.rc, &b.rc)
+    }
+
+    /// Gives back the MyRc inside, for the MyRc only functions like downgrade.
+    pub fn into_rc(this: MyRcCell<T>) -> MyRc<MyRefCell<T>> {
+        this.rc
+    }
+}
+
+impl<T> Clone for MyRcCell<T> {
+    fn clone(&self) -> Self {
+        MyRcCell {
+            rc: self.rc.clone(),
+        }
+    }
+}
+
+impl<T> Deref for MyRcCell<T> {
+    type Target = MyRefCell<T>;
+    fn deref(&self) -> &Self::Target {
+        &self.rc
+    }
+}
+
+impl<T> From<MyRc<MyRefCell<T>>> for MyRcCell<T> {
+    fn from(rc: MyRc<MyRefCell<T>>) -> Self {
+        MyRcCell { rc }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyRcCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.rc, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::{format, vec, vec::Vec};
+
+    struct Node {
+        value: i32,
+        next: Option<MyRcCell<Node>>,
+    }
+
+    #[test]
+    fn mutation_is_seen_through_every_clone() {
+        let tail = MyRcCell::new(Node {
+            value: 2,
+            next: None,
+        });
+        let head = MyRcCell::new(Node {
+            value: 1,
+            next: Some(tail.clone()),
+        });
+        assert_eq!(MyRcCell::strong_count(&tail), 2);
+
+        tail.borrow_mut().value = 20;
+        let through_head = head.borrow().next.as_ref().unwrap().borrow().value;
+        assert_eq!(through_head, 20);
+
+        let next = head.borrow().next.clone().unwrap();
+        assert!(MyRcCell::ptr_eq(&next, &tail));
+        next.borrow_mut().value += 1;
+        assert_eq!(tail.borrow().value, 21);
+
+        let mut values = Vec::new();
+        let mut node = Some(head.clone());
+        while let Some(current) = node {
+            values.push(current.borrow().value);
+            node = current.borrow().next.clone();
+        }
+        assert_eq!(values, vec![1, 21]);
+    }
+
+    #[test]
+    fn borrow_rules_are_shared_between_clones() {
+        let a = MyRcCell::new(5);
+        let b = a.clone();
+
+        let reading = a.borrow();
+        assert!(b.try_borrow_mut().is_err());
+        drop(reading);
+
+        b.replace(6);
+        assert_eq!(*a.borrow(), 6);
+    }
+
+    #[test]
+    fn into_rc_and_back() {
+        let cell = MyRcCell::new(1);
+        let other = cell.clone();
+
+        let rc = MyRcCell::into_rc(cell);
+        let weak = MyRc::downgrade(&rc);
+        let cell: MyRcCell<i32> = rc.into();
+
+        assert!(MyRcCell::ptr_eq(&cell, &other));
+        assert_eq!(*weak.upgrade().unwrap().borrow(), 1);
+
+        drop(cell);
+        drop(other);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn formatting_goes_through_the_refcell() {
+        let cell = MyRcCell::new(3);
+        assert_eq!(format!("{cell:?}"), format!("{:?}", MyRefCell::new(3)));
+    }
+}
@@ -2,142 +2,1426 @@
 //! With most essential info about it
 
 use crate::cell::MyCell;
-use std::{
+use core::{
     cell::UnsafeCell,
-    ops::{Deref, DerefMut},
+    error::Error,
+    fmt,
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut, Index},
+    ptr::{self, NonNull},
 };
 
+#[cfg(feature = "debug_refcell")]
+use core::panic::Location;
+
 /// # Required to wrap value T in UnsafeCell
 /// Because you are never allowed to cast a shared ref to an exclusive ref
 /// in other way than by going through the unsafe cell.
-
-/// # Wrapping our RefState in Cell
-/// Will give us ability to mutate Enum's reference count through a shared reference
-
+///
+/// # Wrapping our borrow flag in Cell
+/// Will give us ability to mutate the reference count through a shared reference
+///
+/// # Borrow flag as a single isize
+/// Same encoding std uses, so the refcell doesn't pay for an enum discriminant:
+/// 0 means unshared, positive is the number of shared borrows
+/// and negative is the number of exclusive (split) borrows.
+///
 /// # Info
 /// RefCell will enforce borrow rules at runtime.
-
+///
+/// # Borrow locations
+/// With the debug_refcell feature the cell also remembers where
+/// the borrow that currently holds it was taken (the first one, for shared borrows),
+/// and a conflicting borrow reports it, e.g "already borrowed: BorrowMutError at src/foo.rs:42:13".
+///
 /// # Common Usage
 /// A fairly safe way to dynamically borrow data
 /// e.g Node in a graph/tree.
-
 pub struct MyRefCell<T> {
     value: UnsafeCell<T>,
-    reference: MyCell<RefState>,
+    reference: MyCell<BorrowFlag>,
+    #[cfg(feature = "debug_refcell")]
+    borrowed_at: MyCell<Option<&'static Location<'static>>>,
+}
+
+type BorrowFlag = isize;
+const UNSHARED: BorrowFlag = 0;
+
+fn is_reading(flag: BorrowFlag) -> bool {
+    flag > UNSHARED
+}
+
+fn is_writing(flag: BorrowFlag) -> bool {
+    flag < UNSHARED
 }
 
-#[derive(Copy, Clone)]
+/// # Required to check for overflow
+/// If the shared count wrapped around it would turn into an exclusive borrow
+/// while shared refs are still alive, so we panic instead.
+#[track_caller]
+fn check_shared_overflow(flag: BorrowFlag) {
+    assert!(flag != MAX_SHARED, "too many shared borrows");
+}
+
+#[cfg(not(test))]
+const MAX_SHARED: BorrowFlag = BorrowFlag::MAX;
+
+/// Lowered in tests, so the overflow branch is reachable.
+#[cfg(test)]
+const MAX_SHARED: BorrowFlag = 1 << 10;
+
+/// # Info
+/// Readable view of the borrow flag, given out by MyRefCell::borrow_state.
+///
+/// Exclusive keeps count of RefMut guards too, since RefMut::map_split
+/// can split one exclusive borrow into guards over disjoint parts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum RefState {
     Shared(usize),
     Unshared,
-    Exclusive,
+    Exclusive(usize),
+}
+
+impl RefState {
+    fn from_flag(flag: BorrowFlag) -> Self {
+        if is_reading(flag) {
+            RefState::Shared(flag.unsigned_abs())
+        } else if is_writing(flag) {
+            RefState::Exclusive(flag.unsigned_abs())
+        } else {
+            RefState::Unshared
+        }
+    }
 }
 
 impl<T> MyRefCell<T> {
-    pub fn new(value: T) -> Self {
+    /// # Info
+    /// const, so it can be used for consts and const thread_local! initializers.
+    /// Same as MyCell, it can't be a `static` since it isn't Sync.
+    pub const fn new(value: T) -> Self {
         MyRefCell {
             value: UnsafeCell::new(value),
-            reference: MyCell::new(RefState::Unshared),
+            reference: MyCell::new(UNSHARED),
+            #[cfg(feature = "debug_refcell")]
+            borrowed_at: MyCell::new(None),
         }
     }
 
-    pub fn borrow(&self) -> Option<Ref<'_, T>> {
-        match self.reference.get() {
-            RefState::Unshared => {
-                self.reference.set(RefState::Shared(1));
-                // SAFE because there are no other refs set yet
-                Some(Ref { refcell: self })
+    #[cfg_attr(feature = "debug_refcell", track_caller)]
+    pub fn try_borrow(&self) -> Result<Ref<'_, T>, BorrowError> {
+        let flag = self.reference.get();
+        if is_writing(flag) {
+            return Err(self.borrow_error());
+        }
 
-                // Some(unsafe { Ref { refcell: &*self } })
-            }
-            RefState::Shared(n) => {
-                self.reference.set(RefState::Shared(n + 1));
-                // SAFE because we can have multiple immutable borrows
+        check_shared_overflow(flag);
+        self.reference.set(flag + 1);
 
-                Some(Ref { refcell: self })
+        // only the first shared borrow is remembered,
+        // it's the one that keeps the cell borrowed the longest
+        #[cfg(feature = "debug_refcell")]
+        if flag == UNSHARED {
+            self.borrowed_at.set(Some(Location::caller()));
+        }
 
-                // Some(unsafe { Ref { refcell: &*self } })
-            }
-            _ => None,
+        // SAFE because there are no exclusive refs and
+        // we can have multiple immutable borrows
+        Ok(self.new_ref())
+    }
+
+    /// # Info
+    /// Gives a plain &T instead of a Ref, after checking
+    /// that the value isn't mutably borrowed right now.
+    ///
+    /// The borrow flag is not touched, so the cell has no idea this ref exists
+    /// and will happily hand out a RefMut while it's still alive.
+    ///
+    /// # Safety
+    /// The caller has to make sure no RefMut is taken (borrow_mut, replace,
+    /// swap, ...) for as long as the returned ref is used.
+    /// Shared borrows are fine, they never conflict with it.
+    ///
+    /// # Common Usage
+    /// Cases where the caller tracks the borrow lifetime itself,
+    /// e.g handing out refs from a cell that's known to be read only at that point.
+    pub unsafe fn try_borrow_unguarded(&self) -> Result<&T, BorrowError> {
+        if is_writing(self.reference.get()) {
+            return Err(self.borrow_error());
+        }
+
+        // SAFE because there is no RefMut right now,
+        // and the caller promises not to take one while the ref lives
+        Ok(unsafe { &*self.value.get() })
+    }
+
+    fn borrow_error(&self) -> BorrowError {
+        BorrowError {
+            _private: (),
+            #[cfg(feature = "debug_refcell")]
+            location: self.borrowed_at.get(),
+        }
+    }
+
+    /// Only to be called after the shared count has been bumped,
+    /// the returned Ref will decrement it on drop.
+    fn new_ref(&self) -> Ref<'_, T> {
+        Ref {
+            // SAFE because UnsafeCell::get never gives a nullptr
+            value: unsafe { NonNull::new_unchecked(self.value.get()) },
+            borrow: BorrowRef {
+                reference: &self.reference,
+            },
+        }
+    }
+
+    /// # Panics
+    /// If the value is currently mutably borrowed.
+    /// Use try_borrow for the non panicking version.
+    #[track_caller]
+    pub fn borrow(&self) -> Ref<'_, T> {
+        match self.try_borrow() {
+            Ok(r) => r,
+            Err(err) => panic!("already mutably borrowed: {:?}", err),
         }
     }
 
-    pub fn borrow_mut(&self) -> Option<RefMut<'_, T>> {
+    /// # Info
+    /// Borrows the cell and narrows the Ref down to one element,
+    /// the whole collection stays borrowed until the Ref is dropped.
+    ///
+    /// # Panics
+    /// If the value is currently mutably borrowed, or i is out of bounds.
+    ///
+    /// # Common Usage
+    /// Reading one node out of a MyRefCell<Vec<Node>>.
+    #[track_caller]
+    pub fn borrow_index(&self, i: usize) -> Ref<'_, T::Output>
+    where
+        T: Index<usize>,
+    {
+        Ref::map(self.borrow(), |value| &value[i])
+    }
+
+    /// # Info
+    /// Borrows the cell and iterates over the elements of the collection inside.
+    ///
+    /// Every item is its own Ref, so the cell stays borrowed until
+    /// the iterator and all the items it gave out are dropped.
+    ///
+    /// # Panics
+    /// If the value is currently mutably borrowed.
+    #[track_caller]
+    pub fn iter_borrowed<E>(&self) -> RefIter<'_, E>
+    where
+        T: AsRef<[E]>,
+    {
+        RefIter {
+            slice: Ref::map(self.borrow(), |value| value.as_ref()),
+            index: 0,
+        }
+    }
+
+    #[cfg_attr(feature = "debug_refcell", track_caller)]
+    pub fn try_borrow_mut(&self) -> Result<RefMut<'_, T>, BorrowMutError> {
         match self.reference.get() {
-            RefState::Unshared => {
+            UNSHARED => {
                 // SAFE because we made sure there a no other refs yet
                 // we can only have one exclusive borrow
-                self.reference.set(RefState::Exclusive);
+                self.reference.set(UNSHARED - 1);
+
+                #[cfg(feature = "debug_refcell")]
+                self.borrowed_at.set(Some(Location::caller()));
+
+                Ok(RefMut {
+                    // SAFE because UnsafeCell::get never gives a nullptr
+                    value: unsafe { NonNull::new_unchecked(self.value.get()) },
+                    borrow: BorrowRefMut {
+                        reference: &self.reference,
+                    },
+                    _marker: PhantomData,
+                })
+            }
 
-                Some(RefMut { refcell: self })
+            flag => Err(BorrowMutError {
+                reentrant: is_writing(flag),
+                #[cfg(feature = "debug_refcell")]
+                location: self.borrowed_at.get(),
+            }),
+        }
+    }
+
+    /// # Panics
+    /// If the value is currently borrowed, shared or mutably.
+    /// Use try_borrow_mut for the non panicking version.
+    #[track_caller]
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        match self.try_borrow_mut() {
+            Ok(r) => r,
+            Err(err) if err.is_reentrant() => {
+                panic!(
+                    "already mutably borrowed, likely a reentrant call: {:?}",
+                    err
+                )
             }
+            Err(err) => panic!("already borrowed: {:?}", err),
+        }
+    }
 
-            _ => None,
+    /// # Info
+    /// Puts the new value in and hands back the old one.
+    ///
+    /// # Panics
+    /// If the value is currently borrowed.
+    #[track_caller]
+    pub fn replace(&self, value: T) -> T {
+        mem::replace(&mut *self.borrow_mut(), value)
+    }
+
+    /// # Info
+    /// Computes the new value from a mutable ref to the old one,
+    /// puts it in and hands back the old value.
+    ///
+    /// # Panics
+    /// If the value is currently borrowed.
+    #[track_caller]
+    pub fn replace_with<F: FnOnce(&mut T) -> T>(&self, f: F) -> T {
+        let mut borrow = self.borrow_mut();
+        let new = f(&mut borrow);
+        mem::replace(&mut *borrow, new)
+    }
+
+    /// # Info
+    /// Runs f with a mutable ref to the value and gives back what f returns.
+    ///
+    /// The RefMut lives only for the call, so it's released before modify
+    /// returns, even if the result is kept around for longer.
+    ///
+    /// # Common Usage
+    /// Short mutations like `cell.modify(|v| v.pop())`, instead of a
+    /// borrow_mut guard that has to be dropped by hand.
+    ///
+    /// # Panics
+    /// If the value is currently borrowed.
+    #[track_caller]
+    pub fn modify<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        f(&mut self.borrow_mut())
+    }
+
+    /// # Info
+    /// Takes the value out and leaves T::default() in its place.
+    ///
+    /// # Panics
+    /// If the value is currently borrowed.
+    #[track_caller]
+    pub fn take(&self) -> T
+    where
+        T: Default,
+    {
+        self.replace(T::default())
+    }
+
+    /// # Info
+    /// Swaps the values of two cells.
+    ///
+    /// Swapping a cell with itself does nothing, same as MyCell::swap.
+    ///
+    /// # Panics
+    /// If either of the values is currently borrowed.
+    #[track_caller]
+    pub fn swap(&self, other: &Self) {
+        if ptr::eq(self, other) {
+            return;
         }
+
+        mem::swap(&mut *self.borrow_mut(), &mut *other.borrow_mut());
+    }
+
+    /// # Info
+    /// Current borrow state of the cell, without taking a borrow.
+    ///
+    /// Handy when tracking down "already borrowed" bugs in tree/graph code.
+    pub fn borrow_state(&self) -> RefState {
+        RefState::from_flag(self.reference.get())
+    }
+
+    /// Consumes the cell and gives back the value inside.
+    pub fn into_inner(self) -> T {
+        // SAFE because we own the cell, so there can't be
+        // any Ref or RefMut still alive
+        self.value.into_inner()
+    }
+
+    /// # Info
+    /// Gives a mutable ref to the value without touching the borrow flag.
+    ///
+    /// Sound because &mut self already guarantees at compile time that
+    /// no Ref or RefMut can be alive, so it can never fail at runtime.
+    pub fn get_mut(&mut self) -> &mut T {
+        // SAFE because &mut self guarantees exclusive access
+        unsafe { &mut *self.value.get() }
     }
 }
 
+/// # Raw borrow primitives
+/// The same flag transitions Ref and RefMut do, for building your own guard types.
+/// The flag itself stays private, these four calls are the only way to move it,
+/// so the cell can't be put into a state the built-in guards don't know about.
+///
+/// A custom guard calls try_acquire_* when it's created, reaches the value
+/// through as_ptr, and calls the matching release_* in its Drop.
+impl<T> MyRefCell<T> {
+    /// # Info
+    /// Takes a shared borrow without a Ref, false if the value is mutably borrowed.
+    ///
+    /// Forgetting to release it is safe, the cell just stays borrowed.
+    ///
+    /// # Panics
+    /// If there are too many shared borrows, same as borrow.
+    #[cfg_attr(feature = "debug_refcell", track_caller)]
+    pub fn try_acquire_shared(&self) -> bool {
+        // the Ref did all the bookkeeping, we only skip its Drop
+        self.try_borrow().map(mem::forget).is_ok()
+    }
+
+    /// # Info
+    /// Takes the exclusive borrow without a RefMut, false if the value is borrowed.
+    #[cfg_attr(feature = "debug_refcell", track_caller)]
+    pub fn try_acquire_exclusive(&self) -> bool {
+        self.try_borrow_mut().map(mem::forget).is_ok()
+    }
+
+    /// # Info
+    /// Gives back a shared borrow taken with try_acquire_shared.
+    ///
+    /// Debug builds panic if the cell isn't shared borrowed at all.
+    ///
+    /// # Safety
+    /// The caller has to own a shared borrow it hasn't released yet,
+    /// and must not use the value through it afterwards.
+    /// Releasing a borrow that belongs to a Ref would let a RefMut in
+    /// while that Ref is still reading.
+    pub unsafe fn release_shared(&self) {
+        let flag = self.reference.get();
+        debug_assert!(is_reading(flag), "release_shared without a shared borrow");
+        self.reference.set(flag - 1);
+    }
+
+    /// # Info
+    /// Gives back the exclusive borrow taken with try_acquire_exclusive.
+    ///
+    /// Debug builds panic if the cell isn't mutably borrowed at all.
+    ///
+    /// # Safety
+    /// Same as release_shared, but for the exclusive borrow.
+    pub unsafe fn release_exclusive(&self) {
+        let flag = self.reference.get();
+        debug_assert!(
+            is_writing(flag),
+            "release_exclusive without an exclusive borrow"
+        );
+        self.reference.set(flag + 1);
+    }
+
+    /// # Info
+    /// Gives a raw pointer to the value inside, same as MyCell::as_ptr.
+    ///
+    /// Creating it is safe, reading or writing through it is only sound
+    /// while you hold the matching acquired borrow.
+    pub fn as_ptr(&self) -> *mut T {
+        self.value.get()
+    }
+}
+
+impl<T> MyRefCell<Option<T>> {
+    /// # Info
+    /// Mutably borrows the cell, fills a None with init() first,
+    /// and gives a RefMut straight to the T inside.
+    ///
+    /// init only runs when the value is None, later calls reuse the stored value.
+    /// The exclusive borrow is held while init runs, so init touching
+    /// the same cell panics like any other reentrant borrow.
+    ///
+    /// # Panics
+    /// If the value is currently borrowed, shared or mutably.
+    ///
+    /// # Common Usage
+    /// Lazy fields, e.g a cache that's only built the first time it's needed.
+    #[track_caller]
+    pub fn borrow_mut_or_init<F: FnOnce() -> T>(&self, init: F) -> RefMut<'_, T> {
+        RefMut::map(self.borrow_mut(), |value| value.get_or_insert_with(init))
+    }
+}
+
+/// # Info
+/// Prints the value when it can be borrowed, and `<borrowed>` while
+/// it's mutably borrowed, so formatting never panics.
+impl<T: fmt::Debug> fmt::Debug for MyRefCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct BorrowedPlaceholder;
+
+        impl fmt::Debug for BorrowedPlaceholder {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("<borrowed>")
+            }
+        }
+
+        match self.try_borrow() {
+            Ok(borrow) => f
+                .debug_struct("MyRefCell")
+                .field("value", &*borrow)
+                .finish(),
+            Err(_) => f
+                .debug_struct("MyRefCell")
+                .field("value", &BorrowedPlaceholder)
+                .finish(),
+        }
+    }
+}
+
+/// # Info
+/// Gives a new, independent cell holding a clone of the current value.
+///
+/// # Panics
+/// If the value is currently mutably borrowed, since we need a shared borrow to clone it.
+impl<T: Clone> Clone for MyRefCell<T> {
+    #[track_caller]
+    fn clone(&self) -> Self {
+        MyRefCell::new(self.borrow().clone())
+    }
+
+    /// &mut self means our own cell can't be borrowed, only source needs checking.
+    #[track_caller]
+    fn clone_from(&mut self, source: &Self) {
+        self.get_mut().clone_from(&source.borrow());
+    }
+}
+
+impl<T: Default> Default for MyRefCell<T> {
+    fn default() -> Self {
+        MyRefCell::new(T::default())
+    }
+}
+
+impl<T> From<T> for MyRefCell<T> {
+    fn from(value: T) -> Self {
+        MyRefCell::new(value)
+    }
+}
+
+/// # Info
+/// Compares the values inside, taking a shared borrow of both cells.
+///
+/// # Panics
+/// If either cell is currently mutably borrowed,
+/// which includes comparing a cell with itself while a RefMut to it is alive.
+impl<T: PartialEq> PartialEq for MyRefCell<T> {
+    #[track_caller]
+    fn eq(&self, other: &Self) -> bool {
+        *self.borrow() == *other.borrow()
+    }
+}
+
+impl<T: Eq> Eq for MyRefCell<T> {}
+
+/// # Info
+/// Read only cousin of MyRefCell: shared borrows nest as deep as you like,
+/// and there is no borrow_mut at all, so the value can't change through a shared ref.
+///
+/// Since nobody can ever write through &self, borrow can't fail
+/// (besides the count overflowing), and the value doesn't need an UnsafeCell.
+/// The borrow count is still kept, so you can see how many Refs are out.
+///
+/// Hands out the same Ref type as MyRefCell, so Ref::map and friends work as usual.
+///
+/// # Common Usage
+/// Tree walking code that borrows the same node again
+/// while it's already reading it, e.g a visitor that recurses through parents.
+pub struct MyReentrantRefCell<T> {
+    value: T,
+    reference: MyCell<BorrowFlag>,
+}
+
+impl<T> MyReentrantRefCell<T> {
+    pub const fn new(value: T) -> Self {
+        MyReentrantRefCell {
+            value,
+            reference: MyCell::new(UNSHARED),
+        }
+    }
+
+    /// # Info
+    /// Always succeeds, a shared borrow can never conflict with another one.
+    #[track_caller]
+    pub fn borrow(&self) -> Ref<'_, T> {
+        let flag = self.reference.get();
+        check_shared_overflow(flag);
+        self.reference.set(flag + 1);
+        Ref {
+            value: NonNull::from(&self.value),
+            borrow: BorrowRef {
+                reference: &self.reference,
+            },
+        }
+    }
+
+    /// Either Unshared or Shared(n), never Exclusive.
+    pub fn borrow_state(&self) -> RefState {
+        RefState::from_flag(self.reference.get())
+    }
+
+    /// &mut self means no Ref is alive, so mutating here is fine.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    /// Consumes the cell and gives back the value inside.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyReentrantRefCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MyReentrantRefCell")
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+/// # Error returned by try_borrow
+/// The value is already mutably borrowed, so a shared borrow
+/// would break the borrow rules.
+pub struct BorrowError {
+    _private: (),
+    // where the RefMut that's in the way was taken
+    #[cfg(feature = "debug_refcell")]
+    location: Option<&'static Location<'static>>,
+}
+
+impl fmt::Debug for BorrowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BorrowError")?;
+        #[cfg(feature = "debug_refcell")]
+        write_location(f, self.location)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for BorrowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("already mutably borrowed")?;
+        #[cfg(feature = "debug_refcell")]
+        write_location(f, self.location)?;
+        Ok(())
+    }
+}
+
+impl Error for BorrowError {}
+
+/// # Error returned by try_borrow_mut
+/// The value is already borrowed, shared or mutably,
+/// so an exclusive borrow would break the borrow rules.
+///
+/// # Reentrant borrows
+/// MyRefCell never leaves its thread, so if the borrow in the way is
+/// another RefMut, the code that holds it is further up our own call stack,
+/// e.g a method that calls itself and borrows the same cell mutably again.
+/// That case gets its own message, since it's a bug in the call structure
+/// rather than an unlucky overlap with a Ref.
+pub struct BorrowMutError {
+    // the borrow in the way is a RefMut, not a Ref
+    reentrant: bool,
+    // where the borrow that's in the way was taken
+    #[cfg(feature = "debug_refcell")]
+    location: Option<&'static Location<'static>>,
+}
+
+impl fmt::Debug for BorrowMutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BorrowMutError")?;
+        #[cfg(feature = "debug_refcell")]
+        write_location(f, self.location)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for BorrowMutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.reentrant {
+            f.write_str("already mutably borrowed, likely a reentrant call")?;
+        } else {
+            f.write_str("already borrowed")?;
+        }
+        #[cfg(feature = "debug_refcell")]
+        write_location(f, self.location)?;
+        Ok(())
+    }
+}
+
+impl BorrowMutError {
+    /// True if the borrow in the way is a RefMut, see Reentrant borrows above.
+    pub fn is_reentrant(&self) -> bool {
+        self.reentrant
+    }
+}
+
+#[cfg(feature = "debug_refcell")]
+fn write_location(
+    f: &mut fmt::Formatter<'_>,
+    location: Option<&'static Location<'static>>,
+) -> fmt::Result {
+    match location {
+        Some(location) => write!(f, " at {}", location),
+        None => Ok(()),
+    }
+}
+
+impl Error for BorrowMutError {}
+
 /// # Ref type as output for borrow method
 /// We need this type to handle decrementing shared ref count
 /// after they go out of scope
-pub struct Ref<'refcell, T> {
-    refcell: &'refcell MyRefCell<T>,
+///
+/// # Storing a pointer instead of the refcell
+/// The pointer can be narrowed with Ref::map to a part of the value,
+/// while the borrow handle keeps the shared count of the whole refcell.
+pub struct Ref<'refcell, T: ?Sized> {
+    value: NonNull<T>,
+    borrow: BorrowRef<'refcell>,
+}
+
+/// # Shared borrow handle
+/// Decrements shared ref count of the refcell when it goes out of scope
+struct BorrowRef<'refcell> {
+    reference: &'refcell MyCell<BorrowFlag>,
 }
 
 /// # Ref type as output for borrow_mut method
 /// We need this to handle decrementing exclusive ref count
 /// after they go out of scope
-pub struct RefMut<'refcell, T> {
-    refcell: &'refcell MyRefCell<T>,
+///
+/// # Required to use PhantomData
+/// NonNull<T> is covariant, but a RefMut behaves like &mut T,
+/// which has to be invariant over T.
+pub struct RefMut<'refcell, T: ?Sized> {
+    value: NonNull<T>,
+    borrow: BorrowRefMut<'refcell>,
+    _marker: PhantomData<&'refcell mut T>,
+}
+
+/// # Exclusive borrow handle
+/// Resets the refcell to Unshared when the last one goes out of scope
+struct BorrowRefMut<'refcell> {
+    reference: &'refcell MyCell<BorrowFlag>,
 }
 
-impl<T> Drop for Ref<'_, T> {
+impl Drop for BorrowRef<'_> {
     fn drop(&mut self) {
-        if let RefState::Shared(n) = self.refcell.reference.get() {
-            if n > 1 {
-                self.refcell.reference.set(RefState::Shared(n - 1));
-            } else {
-                self.refcell.reference.set(RefState::Unshared);
-            }
-        } else {
-            unreachable!()
+        let flag = self.reference.get();
+        debug_assert!(is_reading(flag));
+        self.reference.set(flag - 1);
+    }
+}
+
+impl Clone for BorrowRef<'_> {
+    fn clone(&self) -> Self {
+        let flag = self.reference.get();
+        debug_assert!(is_reading(flag));
+        check_shared_overflow(flag);
+        self.reference.set(flag + 1);
+
+        BorrowRef {
+            reference: self.reference,
+        }
+    }
+}
+
+impl<'refcell, T: ?Sized> Ref<'refcell, T> {
+    /// # Info
+    /// Explicit version of Deref, for code that doesn't want to rely on coercion.
+    ///
+    /// Called as Ref::get(&r), not r.get(), so it doesn't shadow get on T
+    /// (e.g a Ref<Vec<T>> keeps its r.get(index)).
+    pub fn get<'a>(this: &'a Ref<'refcell, T>) -> &'a T {
+        // SAFE because the shared borrow is held for as long as the Ref lives
+        unsafe { this.value.as_ref() }
+    }
+
+    /// # Info
+    /// Gives another Ref to the same value, bumping the shared count,
+    /// so both of them decrement it on drop.
+    ///
+    /// It's an associated function so it doesn't shadow clone on T.
+    #[allow(clippy::should_implement_trait)]
+    pub fn clone(orig: &Ref<'refcell, T>) -> Ref<'refcell, T> {
+        Ref {
+            value: orig.value,
+            borrow: orig.borrow.clone(),
+        }
+    }
+
+    /// # Info
+    /// Narrows the Ref to a part of the borrowed value, e.g a field.
+    ///
+    /// The borrow of the whole refcell stays alive until the new Ref is dropped.
+    ///
+    /// It's an associated function so it doesn't shadow a map method on T.
+    pub fn map<U: ?Sized, F: FnOnce(&T) -> &U>(orig: Ref<'refcell, T>, f: F) -> Ref<'refcell, U> {
+        Ref {
+            value: NonNull::from(f(&*orig)),
+            borrow: orig.borrow,
+        }
+    }
+
+    /// # Info
+    /// Like Ref::map, but the projection may fail, e.g when borrowing into
+    /// an enum variant that might not match.
+    ///
+    /// On failure the original Ref is given back in Err, so the borrow isn't lost.
+    pub fn filter_map<U: ?Sized, F: FnOnce(&T) -> Option<&U>>(
+        orig: Ref<'refcell, T>,
+        f: F,
+    ) -> Result<Ref<'refcell, U>, Ref<'refcell, T>> {
+        match f(&*orig).map(NonNull::from) {
+            Some(value) => Ok(Ref {
+                value,
+                borrow: orig.borrow,
+            }),
+            None => Err(orig),
         }
     }
+
+    /// # Info
+    /// Splits the Ref into two Refs to different parts of the borrowed value.
+    ///
+    /// The shared count gets bumped, so each of them releases
+    /// its part of the borrow independently.
+    pub fn map_split<U: ?Sized, V: ?Sized, F: FnOnce(&T) -> (&U, &V)>(
+        orig: Ref<'refcell, T>,
+        f: F,
+    ) -> (Ref<'refcell, U>, Ref<'refcell, V>) {
+        let (a, b) = f(&*orig);
+        let borrow = orig.borrow.clone();
+        (
+            Ref {
+                value: NonNull::from(a),
+                borrow,
+            },
+            Ref {
+                value: NonNull::from(b),
+                borrow: orig.borrow,
+            },
+        )
+    }
 }
 
-impl<T> Deref for Ref<'_, T> {
+/// # Iterator type as output for iter_borrowed method
+/// Holds a Ref to the whole slice, so the shared borrow outlives
+/// every item, even if the iteration stops early.
+pub struct RefIter<'refcell, E> {
+    slice: Ref<'refcell, [E]>,
+    index: usize,
+}
+
+impl<'refcell, E> Iterator for RefIter<'refcell, E> {
+    type Item = Ref<'refcell, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index == self.slice.len() {
+            return None;
+        }
+
+        let index = self.index;
+        self.index += 1;
+        // a clone of our Ref, so the item keeps the borrow alive on its own
+        Some(Ref::map(Ref::clone(&self.slice), |slice| &slice[index]))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.slice.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<E> ExactSizeIterator for RefIter<'_, E> {}
+
+impl<T: ?Sized> Deref for Ref<'_, T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
         // SAFE because ref is only created when there are
         // no exclusive refs given out
-        unsafe { &*self.refcell.value.get() }
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl BorrowRefMut<'_> {
+    /// Only used to split one exclusive borrow into guards over
+    /// disjoint parts of the value, so it's not a Clone impl.
+    fn split(&self) -> Self {
+        let flag = self.reference.get();
+        debug_assert!(is_writing(flag));
+        assert!(flag != BorrowFlag::MIN, "too many exclusive borrows");
+        self.reference.set(flag - 1);
+
+        BorrowRefMut {
+            reference: self.reference,
+        }
+    }
+}
+
+impl<'refcell, T: ?Sized> RefMut<'refcell, T> {
+    /// # Info
+    /// Explicit version of DerefMut, for code that doesn't want to rely on coercion.
+    ///
+    /// Called as RefMut::get_mut(&mut r), not r.get_mut(),
+    /// so it doesn't shadow get_mut on T.
+    pub fn get_mut<'a>(this: &'a mut RefMut<'refcell, T>) -> &'a mut T {
+        // SAFE because the exclusive borrow is held for as long as the RefMut lives,
+        // and &mut this means nobody else is using it
+        unsafe { this.value.as_mut() }
+    }
+
+    /// # Info
+    /// Narrows the RefMut to a part of the borrowed value, e.g a field.
+    ///
+    /// The exclusive borrow of the whole refcell stays alive
+    /// until the new RefMut is dropped.
+    pub fn map<U: ?Sized, F: FnOnce(&mut T) -> &mut U>(
+        mut orig: RefMut<'refcell, T>,
+        f: F,
+    ) -> RefMut<'refcell, U> {
+        RefMut {
+            value: NonNull::from(f(&mut *orig)),
+            borrow: orig.borrow,
+            _marker: PhantomData,
+        }
+    }
+
+    /// # Info
+    /// Splits the RefMut into two RefMuts to disjoint parts of the borrowed value,
+    /// e.g two halves of a slice.
+    ///
+    /// The refcell stays exclusively borrowed until both of them are dropped.
+    pub fn map_split<U: ?Sized, V: ?Sized, F: FnOnce(&mut T) -> (&mut U, &mut V)>(
+        mut orig: RefMut<'refcell, T>,
+        f: F,
+    ) -> (RefMut<'refcell, U>, RefMut<'refcell, V>) {
+        let borrow = orig.borrow.split();
+        let (a, b) = f(&mut *orig);
+        (
+            RefMut {
+                value: NonNull::from(a),
+                borrow,
+                _marker: PhantomData,
+            },
+            RefMut {
+                value: NonNull::from(b),
+                borrow: orig.borrow,
+                _marker: PhantomData,
+            },
+        )
     }
 }
 
-impl<T> Deref for RefMut<'_, T> {
+impl<T: ?Sized> Deref for RefMut<'_, T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
         // SAFE because ref is only created when there are
         // no exclusive refs given out
-        unsafe { &*self.refcell.value.get() }
+        unsafe { self.value.as_ref() }
     }
 }
 
-impl<T> DerefMut for RefMut<'_, T> {
+impl<T: ?Sized> DerefMut for RefMut<'_, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         // SAFE because ref mut is only created when there are
         // no refs given out.
         // Also it enforces that there will not be any future refs given out
-        unsafe { &mut *self.refcell.value.get() }
+        unsafe { self.value.as_mut() }
     }
 }
 
-impl<T> Drop for RefMut<'_, T> {
+impl Drop for BorrowRefMut<'_> {
     fn drop(&mut self) {
-        if let RefState::Exclusive = self.refcell.reference.get() {
-            self.refcell.reference.set(RefState::Unshared);
-        } else {
-            unreachable!()
+        let flag = self.reference.get();
+        debug_assert!(is_writing(flag));
+        self.reference.set(flag + 1);
+    }
+}
+
+/// # Info
+/// Serializes the value inside transparently, through a shared borrow.
+///
+/// Fails with a serializer error instead of panicking
+/// if the cell is mutably borrowed, same as serde does for std RefCell.
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for MyRefCell<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.try_borrow() {
+            Ok(value) => value.serialize(serializer),
+            Err(_) => Err(serde::ser::Error::custom("already mutably borrowed")),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for MyRefCell<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(MyRefCell::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::{
+        format,
+        panic::{self, AssertUnwindSafe},
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
+
+    #[test]
+    fn try_borrow_fails_only_while_mutably_borrowed() {
+        let cell = MyRefCell::new(1);
+        let first = cell.try_borrow().unwrap();
+        let second = cell.try_borrow().unwrap();
+        assert_eq!(*first + *second, 2);
+        drop((first, second));
+
+        let guard = cell.borrow_mut();
+        let err = cell.try_borrow().err().unwrap();
+        assert!(err.to_string().starts_with("already mutably borrowed"));
+        drop(guard);
+        assert!(cell.try_borrow().is_ok());
+    }
+
+    #[test]
+    fn try_borrow_mut_fails_while_borrowed_either_way() {
+        let cell = MyRefCell::new(1);
+        let shared = cell.borrow();
+        let err = cell.try_borrow_mut().err().unwrap();
+        assert!(!err.is_reentrant());
+        assert!(err.to_string().starts_with("already borrowed"));
+        drop(shared);
+
+        let exclusive = cell.borrow_mut();
+        let err = cell.try_borrow_mut().err().unwrap();
+        assert!(err.is_reentrant());
+        drop(exclusive);
+        assert!(cell.try_borrow_mut().is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "already mutably borrowed: BorrowError")]
+    fn borrow_while_mutably_borrowed_panics() {
+        let cell = MyRefCell::new(1);
+        let _guard = cell.borrow_mut();
+        let _ = cell.borrow();
+    }
+
+    #[test]
+    #[should_panic(expected = "already borrowed: BorrowMutError")]
+    fn borrow_mut_while_borrowed_panics() {
+        let cell = MyRefCell::new(1);
+        let _guard = cell.borrow();
+        let _ = cell.borrow_mut();
+    }
+
+    #[test]
+    #[should_panic(expected = "already mutably borrowed, likely a reentrant call")]
+    fn reentrant_borrow_mut_panics() {
+        let cell = MyRefCell::new(vec![1]);
+        cell.modify(|_| cell.modify(|v| v.push(2)));
+    }
+
+    #[test]
+    fn into_inner_after_a_borrow() {
+        let cell = MyRefCell::new(String::from("inner"));
+        assert_eq!(cell.borrow().len(), 5);
+        assert_eq!(cell.into_inner(), "inner");
+    }
+
+    #[test]
+    fn get_mut_skips_the_flag() {
+        let mut cell = MyRefCell::new(vec![1]);
+        cell.get_mut().push(2);
+        assert_eq!(cell.borrow_state(), RefState::Unshared);
+        assert_eq!(*cell.borrow(), [1, 2]);
+    }
+
+    #[test]
+    fn replace_and_replace_with() {
+        let cell = MyRefCell::new(1);
+        assert_eq!(cell.replace(2), 1);
+        assert_eq!(cell.replace_with(|n| *n * 10), 2);
+        assert_eq!(*cell.borrow(), 20);
+
+        let guard = cell.borrow();
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| cell.replace(3))).is_err());
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| cell.replace_with(|n| *n))).is_err());
+        assert_eq!(*guard, 20);
+    }
+
+    #[test]
+    fn take_leaves_an_empty_vec() {
+        let cell = MyRefCell::new(vec![1, 2]);
+        assert_eq!(cell.take(), [1, 2]);
+        assert!(cell.borrow().is_empty());
+    }
+
+    #[test]
+    fn swap_and_self_swap() {
+        let a = MyRefCell::new(String::from("a"));
+        let b = MyRefCell::new(String::from("b"));
+        a.swap(&b);
+        a.swap(&a);
+        assert_eq!(a.into_inner(), "b");
+        assert_eq!(b.into_inner(), "a");
+    }
+
+    #[test]
+    fn map_keeps_the_borrow_until_the_mapped_ref_drops() {
+        let cell = MyRefCell::new((1, String::from("a")));
+        let first = Ref::map(cell.borrow(), |pair| &pair.0);
+        assert_eq!(*first, 1);
+        assert_eq!(cell.borrow_state(), RefState::Shared(1));
+        drop(first);
+        assert_eq!(cell.borrow_state(), RefState::Unshared);
+    }
+
+    #[test]
+    fn ref_mut_map_into_a_field() {
+        let cell = MyRefCell::new((1, String::from("a")));
+        let mut name = RefMut::map(cell.borrow_mut(), |pair| &mut pair.1);
+        name.push('b');
+        drop(name);
+        assert_eq!(*cell.borrow(), (1, String::from("ab")));
+    }
+
+    #[test]
+    fn ref_clones_release_one_at_a_time() {
+        let cell = MyRefCell::new(1);
+        let first = cell.borrow();
+        let second = Ref::clone(&first);
+        let third = Ref::clone(&second);
+        assert_eq!(cell.borrow_state(), RefState::Shared(3));
+
+        drop(first);
+        drop(third);
+        assert_eq!(cell.borrow_state(), RefState::Shared(1));
+        drop(second);
+        assert_eq!(cell.borrow_state(), RefState::Unshared);
+    }
+
+    #[test]
+    fn map_split_needs_both_halves_dropped() {
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        let cell = MyRefCell::new(Point { x: 1, y: 2 });
+        let (x, y) = Ref::map_split(cell.borrow(), |p| (&p.x, &p.y));
+        assert_eq!(*x + *y, 3);
+        drop(x);
+        assert_eq!(cell.borrow_state(), RefState::Shared(1));
+        drop(y);
+        assert_eq!(cell.borrow_state(), RefState::Unshared);
+    }
+
+    #[test]
+    fn ref_mut_map_split_into_halves() {
+        let cell = MyRefCell::new([1, 2, 3, 4]);
+        let (mut left, mut right) = RefMut::map_split(cell.borrow_mut(), |a| a.split_at_mut(2));
+        left[0] = 10;
+        right[1] = 40;
+        assert_eq!(cell.borrow_state(), RefState::Exclusive(2));
+        drop(left);
+        assert_eq!(cell.borrow_state(), RefState::Exclusive(1));
+        drop(right);
+        assert_eq!(*cell.borrow(), [10, 2, 3, 40]);
+    }
+
+    #[test]
+    fn filter_map_match_and_no_match() {
+        let cell = MyRefCell::new(vec![1, 2]);
+        let first = Ref::filter_map(cell.borrow(), |v| v.first()).ok().unwrap();
+        assert_eq!(*first, 1);
+        assert_eq!(cell.borrow_state(), RefState::Shared(1));
+        drop(first);
+
+        // no match gives the original Ref back, still counted once
+        let orig = Ref::filter_map(cell.borrow(), |v| v.get(5)).err().unwrap();
+        assert_eq!(cell.borrow_state(), RefState::Shared(1));
+        drop(orig);
+        assert_eq!(cell.borrow_state(), RefState::Unshared);
+    }
+
+    #[test]
+    fn debug_never_panics() {
+        let cell = MyRefCell::new(5);
+        assert_eq!(format!("{:?}", cell), "MyRefCell { value: 5 }");
+        let _shared = cell.borrow();
+        assert_eq!(format!("{:?}", cell), "MyRefCell { value: 5 }");
+        drop(_shared);
+
+        let _guard = cell.borrow_mut();
+        assert_eq!(format!("{:?}", cell), "MyRefCell { value: <borrowed> }");
+    }
+
+    #[test]
+    fn borrow_state_follows_the_guards() {
+        let cell = MyRefCell::new(1);
+        assert_eq!(cell.borrow_state(), RefState::Unshared);
+        let a = cell.borrow();
+        let b = cell.borrow();
+        assert_eq!(cell.borrow_state(), RefState::Shared(2));
+        drop((a, b));
+        let guard = cell.borrow_mut();
+        assert_eq!(cell.borrow_state(), RefState::Exclusive(1));
+        drop(guard);
+        assert_eq!(cell.borrow_state(), RefState::Unshared);
+    }
+
+    #[test]
+    fn borrow_flag_is_one_word() {
+        assert_eq!(
+            mem::size_of::<MyRefCell<u64>>(),
+            mem::size_of::<u64>()
+                + mem::size_of::<BorrowFlag>()
+                + if cfg!(feature = "debug_refcell") {
+                    mem::size_of::<usize>()
+                } else {
+                    0
+                }
+        );
+    }
+
+    #[test]
+    fn reentrant_cell_nests_shared_borrows() {
+        let cell = MyReentrantRefCell::new(vec![1, 2]);
+        let outer = cell.borrow();
+        let inner = cell.borrow();
+        let innermost = cell.borrow();
+        assert_eq!(outer.len() + inner.len() + innermost.len(), 6);
+        assert_eq!(cell.borrow_state(), RefState::Shared(3));
+        drop((outer, inner, innermost));
+        assert_eq!(cell.into_inner(), [1, 2]);
+    }
+
+    #[cfg(feature = "debug_refcell")]
+    #[test]
+    fn conflict_reports_where_the_borrow_was_taken() {
+        let cell = MyRefCell::new(1);
+        let line = line!() + 1;
+        let _guard = cell.borrow_mut();
+        let err = cell.try_borrow().err().unwrap();
+        let at = format!("{}:{}", file!(), line);
+        assert!(err.to_string().contains(&at), "{} has no {}", err, at);
+    }
+
+    #[test]
+    fn unguarded_borrow() {
+        let cell = MyRefCell::new(1);
+        // SAFE because no RefMut is taken while the ref is used
+        assert_eq!(unsafe { cell.try_borrow_unguarded() }.ok(), Some(&1));
+
+        let _guard = cell.borrow_mut();
+        // SAFE because the error case gives out no ref at all
+        assert!(unsafe { cell.try_borrow_unguarded() }.is_err());
+    }
+
+    #[test]
+    fn clone_is_independent() {
+        let cell = MyRefCell::new(vec![1]);
+        let clone = cell.clone();
+        clone.borrow_mut().push(2);
+        assert_eq!(*cell.borrow(), [1]);
+
+        let _guard = cell.borrow_mut();
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| cell.clone())).is_err());
+    }
+
+    #[test]
+    fn default_and_from() {
+        #[derive(Default)]
+        struct Node {
+            children: MyRefCell<Vec<i32>>,
+        }
+
+        assert!(Node::default().children.borrow().is_empty());
+        let cell: MyRefCell<Vec<i32>> = vec![1].into();
+        assert_eq!(*cell.borrow(), [1]);
+    }
+
+    #[test]
+    fn eq_compares_the_values() {
+        assert_eq!(MyRefCell::new(1), MyRefCell::new(1));
+        assert_ne!(MyRefCell::new(1), MyRefCell::new(2));
+
+        // comparing a cell with itself while it's mutably borrowed panics,
+        // eq needs a shared borrow of both sides
+        let cell = MyRefCell::new(1);
+        let _guard = cell.borrow_mut();
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| cell == cell)).is_err());
+    }
+
+    #[test]
+    fn explicit_accessors() {
+        let cell = MyRefCell::new(1);
+        let mut guard = cell.borrow_mut();
+        *RefMut::get_mut(&mut guard) += 1;
+        drop(guard);
+        let guard = cell.borrow();
+        assert_eq!(*Ref::get(&guard), 2);
+    }
+
+    #[test]
+    fn borrow_index_holds_the_whole_cell() {
+        let cell = MyRefCell::new(vec![
+            String::from("a"),
+            String::from("b"),
+            String::from("c"),
+        ]);
+        let third = cell.borrow_index(2);
+        assert_eq!(*third, "c");
+        assert!(cell.try_borrow_mut().is_err());
+        drop(third);
+        assert_eq!(cell.borrow_state(), RefState::Unshared);
+    }
+
+    #[test]
+    fn iter_borrowed_releases_after_the_iterator() {
+        let cell = MyRefCell::new(vec![1, 2, 3]);
+        let sum: i32 = cell.iter_borrowed().map(|n| *n).sum();
+        assert_eq!(sum, 6);
+        assert_eq!(cell.borrow_state(), RefState::Unshared);
+
+        // stopping early still holds the cell until the iterator goes away
+        let mut iter = cell.iter_borrowed();
+        let first = iter.next().unwrap();
+        assert_eq!(*first, 1);
+        assert!(cell.try_borrow_mut().is_err());
+        drop(iter);
+        assert!(cell.try_borrow_mut().is_err());
+        drop(first);
+        assert_eq!(cell.borrow_state(), RefState::Unshared);
+    }
+
+    #[test]
+    fn custom_guard_on_the_raw_primitives() {
+        struct ReadGuard<'a>(&'a MyRefCell<i32>);
+
+        impl ReadGuard<'_> {
+            fn get(&self) -> i32 {
+                // SAFE because the guard holds a shared borrow
+                unsafe { *self.0.as_ptr() }
+            }
+        }
+
+        impl Drop for ReadGuard<'_> {
+            fn drop(&mut self) {
+                // SAFE because the guard owns this shared borrow
+                unsafe { self.0.release_shared() }
+            }
         }
+
+        let cell = MyRefCell::new(7);
+        assert!(cell.try_acquire_shared());
+        let guard = ReadGuard(&cell);
+        assert_eq!(guard.get(), 7);
+        assert_eq!(cell.borrow_state(), RefState::Shared(1));
+        assert!(!cell.try_acquire_exclusive());
+        drop(guard);
+        assert_eq!(cell.borrow_state(), RefState::Unshared);
+
+        assert!(cell.try_acquire_exclusive());
+        assert!(!cell.try_acquire_shared());
+        // SAFE because we took the exclusive borrow right above
+        unsafe { cell.release_exclusive() };
+        assert_eq!(cell.borrow_state(), RefState::Unshared);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "release_shared without a shared borrow")]
+    fn releasing_a_borrow_never_taken_panics() {
+        let cell = MyRefCell::new(1);
+        // SAFE to call, the debug check panics before the flag is touched
+        unsafe { cell.release_shared() };
+    }
+
+    #[test]
+    fn borrow_mut_or_init_runs_init_once() {
+        let cell = MyRefCell::new(None);
+        let mut runs = 0;
+        for n in 1..=3 {
+            cell.borrow_mut_or_init(|| {
+                runs += 1;
+                Vec::new()
+            })
+            .push(n);
+        }
+        assert_eq!(runs, 1);
+        assert_eq!(cell.into_inner(), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn modify_returns_the_closure_result() {
+        let cell = MyRefCell::new(vec![1, 2, 3]);
+        assert_eq!(cell.modify(|v| v.pop()), Some(3));
+        assert_eq!(cell.borrow_state(), RefState::Unshared);
+
+        let _guard = cell.borrow();
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| cell.modify(|v| v.len()))).is_err());
+    }
+
+    #[test]
+    fn const_refcell() {
+        std::thread_local! {
+            static LOG: MyRefCell<Vec<i32>> = const { MyRefCell::new(Vec::new()) };
+        }
+
+        LOG.with(|log| log.borrow_mut().push(1));
+        assert_eq!(LOG.with(|log| log.borrow().len()), 1);
+    }
+
+    /// Takes shared borrows and forgets them, until the flag sits at the cap.
+    fn borrow_to_cap(cell: &MyRefCell<i32>) {
+        for _ in 0..MAX_SHARED {
+            mem::forget(cell.borrow());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "too many shared borrows")]
+    fn borrow_past_the_cap_panics() {
+        let cell = MyRefCell::new(1);
+        borrow_to_cap(&cell);
+        let _ = cell.borrow();
+    }
+
+    #[test]
+    fn shared_overflow_never_wraps() {
+        let cell = MyRefCell::new(1);
+        for _ in 1..MAX_SHARED {
+            mem::forget(cell.borrow());
+        }
+        let last = cell.borrow();
+
+        let borrow = panic::catch_unwind(AssertUnwindSafe(|| cell.borrow()));
+        assert!(borrow.is_err());
+        let clone = panic::catch_unwind(AssertUnwindSafe(|| Ref::clone(&last)));
+        assert!(clone.is_err());
+
+        // still counted as shared, a wrapped flag would read as exclusive
+        assert_eq!(cell.borrow_state(), RefState::Shared(MAX_SHARED as usize));
+        assert!(cell.try_borrow_mut().is_err());
+        assert_eq!(*last, 1);
     }
 }
@@ -0,0 +1,427 @@
+//! # Basic implementation of a RwLock spin lock
+//! With most essential info about it
+
+use crate::sync::{hint, AtomicUsize, Ordering};
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+};
+
+/// State word of the lock.
+///
+/// 0 means nobody holds it, and WRITER means a single writer holds it.
+/// Otherwise the low bits count the readers, and two high bits track
+/// the upgradable reader: UPGRADABLE while one is held,
+/// UPGRADING while it waits for the other readers to leave.
+///
+/// The reader count stops below MAX_READERS,
+/// so the state can never look like WRITER by accident.
+const UNLOCKED: usize = 0;
+const WRITER: usize = usize::MAX;
+const UPGRADABLE: usize = 1 << (usize::BITS - 1);
+const UPGRADING: usize = 1 << (usize::BITS - 2);
+const MAX_READERS: usize = UPGRADING - 1;
+
+/// # Required to wrap value T in UnsafeCell
+/// Because you are never allowed to cast a shared ref to an exclusive ref
+/// in other way than by going through the unsafe cell.
+///
+/// # Info
+/// Same rules as MyRefCell, but for many threads:
+/// many readers or one writer at a time.
+///
+/// Instead of failing when the rules would be broken,
+/// read and write spin until the lock can be taken.
+///
+/// Writers are not prioritized, so a steady stream of readers
+/// can keep a writer waiting.
+///
+/// # Upgradable reads
+/// On top of that, one reader at a time can take an upgradable read.
+/// It shares the lock with plain readers, and can later turn into a writer
+/// without letting go of the lock, so nobody can change the value between
+/// reading it and deciding to write.
+/// While it waits for the plain readers to leave, new ones are kept out.
+///
+/// # Common Usage
+/// Shared data that is read a lot and written rarely,
+/// e.g config behind a MyArc.
+pub struct MyRwLock<T> {
+    state: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+
+/// # Required to impl Sync by hand
+/// UnsafeCell is not Sync, so the compiler won't derive it.
+///
+/// T has to be Sync since many readers can look at it from different threads at once,
+/// and Send since a writer on any thread can swap it out.
+unsafe impl<T: Send + Sync> Sync for MyRwLock<T> {}
+
+impl<T> MyRwLock<T> {
+    pub fn new(value: T) -> Self {
+        MyRwLock {
+            state: AtomicUsize::new(UNLOCKED),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// # Info
+    /// Spins until there is no writer, then takes a shared lock.
+    pub fn read(&self) -> MyRwLockReadGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            hint::spin_loop();
+        }
+    }
+
+    /// # Info
+    /// Takes a shared lock if there is no writer right now, None otherwise.
+    pub fn try_read(&self) -> Option<MyRwLockReadGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            // an upgrade in progress keeps new readers out,
+            // otherwise it could wait forever
+            if state == WRITER || state & UPGRADING != 0 || state & MAX_READERS >= MAX_READERS - 1 {
+                return None;
+            }
+
+            // Acquire so we see everything the last writer did with the value
+            match self.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(MyRwLockReadGuard { lock: self }),
+                Err(current) => state = current,
+            }
+        }
+    }
+
+    /// # Info
+    /// Spins until there is no writer and no other upgradable reader,
+    /// then takes an upgradable read.
+    pub fn upgradable_read(&self) -> MyRwLockUpgradableGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_upgradable_read() {
+                return guard;
+            }
+            hint::spin_loop();
+        }
+    }
+
+    /// # Info
+    /// Takes an upgradable read if there is no writer
+    /// and no other upgradable reader right now, None otherwise.
+    pub fn try_upgradable_read(&self) -> Option<MyRwLockUpgradableGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state == WRITER || state & UPGRADABLE != 0 {
+                return None;
+            }
+
+            // Acquire so we see everything the last writer did with the value
+            match self.state.compare_exchange_weak(
+                state,
+                state | UPGRADABLE,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(MyRwLockUpgradableGuard { lock: self }),
+                Err(current) => state = current,
+            }
+        }
+    }
+
+    /// # Info
+    /// Spins until nobody holds the lock, then takes it exclusively.
+    pub fn write(&self) -> MyRwLockWriteGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_write() {
+                return guard;
+            }
+
+            while self.state.load(Ordering::Relaxed) != UNLOCKED {
+                hint::spin_loop();
+            }
+        }
+    }
+
+    /// # Info
+    /// Takes the exclusive lock if nobody holds it right now, None otherwise.
+    pub fn try_write(&self) -> Option<MyRwLockWriteGuard<'_, T>> {
+        // Acquire so we see everything the previous owners did with the value
+        self.state
+            .compare_exchange(UNLOCKED, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| MyRwLockWriteGuard {
+                lock: self,
+                _marker: PhantomData,
+            })
+    }
+}
+
+/// # Guard type as output for read method
+/// We need this type to release the shared lock after it goes out of scope
+pub struct MyRwLockReadGuard<'lock, T> {
+    lock: &'lock MyRwLock<T>,
+}
+
+impl<T> Deref for MyRwLockReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFE because while a read guard is alive there is no writer
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for MyRwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        // Release so a later writer can't reorder its writes before our reads
+        self.lock.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// # Guard type as output for upgradable_read method
+/// We need this type to release the upgradable read after it goes out of scope,
+/// or to turn it into a write guard.
+pub struct MyRwLockUpgradableGuard<'lock, T> {
+    lock: &'lock MyRwLock<T>,
+}
+
+impl<'lock, T> MyRwLockUpgradableGuard<'lock, T> {
+    /// # Info
+    /// Turns into a write guard if there are no plain readers right now,
+    /// otherwise gives the guard back.
+    pub fn try_upgrade(
+        this: MyRwLockUpgradableGuard<'lock, T>,
+    ) -> Result<MyRwLockWriteGuard<'lock, T>, MyRwLockUpgradableGuard<'lock, T>> {
+        // Acquire so we see anything the readers that just left did,
+        // they only read, but they did it before we write
+        match this.lock.state.compare_exchange(
+            UPGRADABLE,
+            WRITER,
+            Ordering::Acquire,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => Ok(MyRwLockUpgradableGuard::into_write(this)),
+            Err(_) => Err(this),
+        }
+    }
+
+    /// # Info
+    /// Turns into a write guard, spinning until the plain readers are gone.
+    ///
+    /// The lock is held the whole time, and no new readers get in
+    /// while we wait, so the value we read is still the value we write to.
+    pub fn upgrade(this: MyRwLockUpgradableGuard<'lock, T>) -> MyRwLockWriteGuard<'lock, T> {
+        // only the upgradable reader ever sets this bit
+        this.lock.state.fetch_or(UPGRADING, Ordering::Relaxed);
+
+        while this
+            .lock
+            .state
+            .compare_exchange_weak(
+                UPGRADABLE | UPGRADING,
+                WRITER,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            hint::spin_loop();
+        }
+
+        MyRwLockUpgradableGuard::into_write(this)
+    }
+
+    /// Only to be called once the state is WRITER on our behalf.
+    fn into_write(this: MyRwLockUpgradableGuard<'lock, T>) -> MyRwLockWriteGuard<'lock, T> {
+        let lock = this.lock;
+        // the upgradable bits are already gone from the state,
+        // our Drop must not clear them again
+        mem::forget(this);
+        MyRwLockWriteGuard {
+            lock,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for MyRwLockUpgradableGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFE because while an upgradable guard is alive there is no writer
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for MyRwLockUpgradableGuard<'_, T> {
+    fn drop(&mut self) {
+        // Release so a later writer can't reorder its writes before our reads
+        self.lock.state.fetch_sub(UPGRADABLE, Ordering::Release);
+    }
+}
+
+/// # Guard type as output for write method
+/// We need this type to release the exclusive lock after it goes out of scope
+///
+/// # Required to use PhantomData
+/// Behaves like &mut T, so sharing the guard between threads
+/// has to require T: Sync.
+pub struct MyRwLockWriteGuard<'lock, T> {
+    lock: &'lock MyRwLock<T>,
+    _marker: PhantomData<&'lock mut T>,
+}
+
+impl<T> Deref for MyRwLockWriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFE because the write guard is the only one holding the lock
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> DerefMut for MyRwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFE because the write guard is the only one holding the lock
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for MyRwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        // Release so the next owners see everything we did with the value
+        self.lock.state.store(UNLOCKED, Ordering::Release);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::{sync::Barrier, thread, vec, vec::Vec};
+
+    #[test]
+    fn readers_share_writers_exclude() {
+        let lock = MyRwLock::new(1);
+        let a = lock.read();
+        let b = lock.try_read().unwrap();
+        assert!(lock.try_write().is_none());
+        assert_eq!(*a + *b, 2);
+        drop((a, b));
+
+        let mut write = lock.try_write().unwrap();
+        *write = 2;
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_upgradable_read().is_none());
+        drop(write);
+        assert_eq!(*lock.read(), 2);
+    }
+
+    #[test]
+    fn readers_never_see_a_half_written_value() {
+        const READERS: usize = 4;
+        let writes = if cfg!(miri) { 20 } else { 500 };
+
+        // a writer keeps both halves equal, a torn read would see them differ
+        let lock = MyRwLock::new((0usize, 0usize));
+        thread::scope(|s| {
+            for _ in 0..READERS {
+                s.spawn(|| loop {
+                    let pair = *lock.read();
+                    assert_eq!(pair.0, pair.1);
+                    if pair.0 == writes {
+                        break;
+                    }
+                });
+            }
+            s.spawn(|| {
+                for n in 1..=writes {
+                    let mut pair = lock.write();
+                    pair.0 = n;
+                    pair.1 = n;
+                }
+            });
+        });
+        assert_eq!(*lock.read(), (writes, writes));
+    }
+
+    #[test]
+    fn upgradable_read_shares_with_readers_but_not_another_upgradable() {
+        let lock = MyRwLock::new(1);
+        let upgradable = lock.upgradable_read();
+        let reader = lock.read();
+        assert!(lock.try_upgradable_read().is_none());
+        assert!(lock.try_write().is_none());
+
+        // a plain reader is still in, so the upgrade has to wait
+        let upgradable = MyRwLockUpgradableGuard::try_upgrade(upgradable)
+            .err()
+            .unwrap();
+        drop(reader);
+        let mut write = MyRwLockUpgradableGuard::try_upgrade(upgradable)
+            .ok()
+            .unwrap();
+        *write += 1;
+        drop(write);
+        assert_eq!(*lock.read(), 2);
+    }
+
+    #[test]
+    fn upgrade_keeps_readers_out_while_writing() {
+        let lock = MyRwLock::new(vec![1]);
+        let barrier = Barrier::new(2);
+        thread::scope(|s| {
+            let reader = lock.read();
+            s.spawn(|| {
+                let upgradable = lock.upgradable_read();
+                // decides to write after reading
+                assert_eq!(*upgradable, [1]);
+                barrier.wait();
+                let mut write = MyRwLockUpgradableGuard::upgrade(upgradable);
+                // nobody else can be in here
+                assert!(lock.try_read().is_none());
+                write.push(2);
+            });
+            barrier.wait();
+            // the upgrade can't finish while this reader is still reading
+            assert_eq!(*reader, [1]);
+            drop(reader);
+        });
+        let values: Vec<_> = lock.read().clone();
+        assert_eq!(values, [1, 2]);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use crate::arc::MyArc;
+    use loom::thread;
+
+    #[test]
+    fn reader_races_an_upgrade() {
+        loom::model(|| {
+            let lock = MyArc::new(MyRwLock::new(0));
+            let other = lock.clone();
+
+            let t = thread::spawn(move || {
+                let value = *other.read();
+                assert!(value == 0 || value == 1);
+            });
+            let mut write = MyRwLockUpgradableGuard::upgrade(lock.upgradable_read());
+            *write += 1;
+            drop(write);
+            t.join().unwrap();
+
+            assert_eq!(*lock.read(), 1);
+        });
+    }
+}
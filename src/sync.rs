@@ -0,0 +1,27 @@
+//! # Atomics used by the thread safe types
+//! Normally just the ones from core, but under cfg(loom) they are swapped
+//! for loom's, so the orderings can be model checked over every interleaving.
+//!
+//! Run the checks with `RUSTFLAGS="--cfg loom" cargo test --release --lib`,
+//! the models are the loom_tests modules next to the types.
+
+#[cfg(not(loom))]
+pub(crate) use core::{
+    hint,
+    sync::atomic::{self, AtomicPtr, AtomicUsize, Ordering},
+};
+
+// only MyMutex and MyCondvar need it, and they are std only
+#[cfg(all(not(loom), feature = "std"))]
+pub(crate) use core::sync::atomic::AtomicBool;
+
+// loom's spin_loop yields to the other model threads,
+// a plain spin would never let the thread holding the lock run
+#[cfg(loom)]
+pub(crate) use loom::{
+    hint,
+    sync::atomic::{self, AtomicPtr, AtomicUsize, Ordering},
+};
+
+#[cfg(all(loom, feature = "std"))]
+pub(crate) use loom::sync::atomic::AtomicBool;
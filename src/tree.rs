@@ -0,0 +1,245 @@
+//! # Tree of shared nodes with weak parent links
+//! With most essential info about it
+
+use alloc::vec::Vec;
+
+use crate::{
+    rc::{MyRc, MyWeak},
+    refcell::{MyRefCell, Ref, RefMut},
+};
+
+/// # Info
+/// Handle to a node of a tree, cloning it gives another handle
+/// to the same node, not a copy of the subtree.
+///
+/// Children are owned through MyRc, the parent is only pointed to with a MyWeak.
+/// So dropping the root (and every other handle into the tree) frees the whole tree.
+///
+/// # Required to use MyWeak for the parent
+/// With MyRc both ways every parent and child would keep each other alive,
+/// and the counts would never reach 0, the tree would leak.
+///
+/// # Required to check for cycles
+/// Adding a node as a child of itself or of one of its descendants
+/// would make a cycle of MyRc, which leaks the same way.
+/// add_child panics instead of building it.
+///
+/// # Common Usage
+/// UI widget trees, DOM like documents, file system like hierarchies.
+pub struct MyTreeNode<T> {
+    node: MyRc<MyRefCell<NodeData<T>>>,
+}
+
+struct NodeData<T> {
+    value: T,
+    parent: MyWeak<MyRefCell<NodeData<T>>>,
+    children: Vec<MyTreeNode<T>>,
+}
+
+impl<T> MyTreeNode<T> {
+    /// A node without parent and children.
+    pub fn new(value: T) -> Self {
+        MyTreeNode {
+            node: MyRc::new(MyRefCell::new(NodeData {
+                value,
+                parent: MyWeak::new(),
+                children: Vec::new(),
+            })),
+        }
+    }
+
+    /// # Info
+    /// Makes child the last child of this node.
+    /// If child already had a parent it's moved out from there first.
+    ///
+    /// # Panics
+    /// If child is this node or one of its ancestors, that would be a cycle.
+    /// Also if one of the nodes involved is currently borrowed.
+    #[track_caller]
+    pub fn add_child(&self, child: MyTreeNode<T>) {
+        let mut ancestor = Some(self.clone());
+        while let Some(node) = ancestor {
+            assert!(
+                !MyTreeNode::ptr_eq(&node, &child),
+                "add_child would create a cycle"
+            );
+            ancestor = node.parent();
+        }
+
+        child.detach();
+        child.node.borrow_mut().parent = MyRc::downgrade(&self.node);
+        self.node.borrow_mut().children.push(child);
+    }
+
+    /// # Info
+    /// Removes this node from its parent's children, it becomes the root of its own tree.
+    /// Does nothing for a node without parent.
+    #[track_caller]
+    pub fn detach(&self) {
+        let Some(parent) = self.parent() else {
+            return;
+        };
+
+        parent
+            .node
+            .borrow_mut()
+            .children
+            .retain(|child| !MyTreeNode::ptr_eq(child, self));
+        self.node.borrow_mut().parent = MyWeak::new();
+    }
+
+    /// The parent node, None for the root.
+    #[track_caller]
+    pub fn parent(&self) -> Option<MyTreeNode<T>> {
+        self.node
+            .borrow()
+            .parent
+            .upgrade()
+            .map(|node| MyTreeNode { node })
+    }
+
+    /// # Info
+    /// Handles to the children, in the order they were added.
+    ///
+    /// Gives clones of the handles instead of a Ref to the Vec,
+    /// so the node isn't kept borrowed while the caller walks down the tree.
+    #[track_caller]
+    pub fn children(&self) -> Vec<MyTreeNode<T>> {
+        self.node.borrow().children.clone()
+    }
+
+    /// # Panics
+    /// If this node is currently mutably borrowed.
+    #[track_caller]
+    pub fn value(&self) -> Ref<'_, T> {
+        Ref::map(self.node.borrow(), |data| &data.value)
+    }
+
+    /// # Panics
+    /// If this node is currently borrowed.
+    #[track_caller]
+    pub fn value_mut(&self) -> RefMut<'_, T> {
+        RefMut::map(self.node.borrow_mut(), |data| &mut data.value)
+    }
+
+    /// Checks if both handles point to the same node.
+    pub fn ptr_eq(a: &MyTreeNode<T>, b: &MyTreeNode<T>) -> bool {
+        MyRc::ptr_eq(&a.node, &b.node)
+    }
+}
+
+impl<T> Clone for MyTreeNode<T> {
+    fn clone(&self) -> Self {
+        MyTreeNode {
+            node: self.node.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::cell::Cell;
+    use std::{vec, vec::Vec};
+
+    struct DropFlag<'a>(&'a Cell<usize>);
+
+    impl Drop for DropFlag<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    fn values(nodes: &[MyTreeNode<i32>]) -> Vec<i32> {
+        nodes.iter().map(|node| *node.value()).collect()
+    }
+
+    #[test]
+    fn navigate_parent_and_children() {
+        let root = MyTreeNode::new(1);
+        let left = MyTreeNode::new(2);
+        let right = MyTreeNode::new(3);
+        let leaf = MyTreeNode::new(4);
+
+        root.add_child(left.clone());
+        root.add_child(right.clone());
+        left.add_child(leaf.clone());
+
+        assert!(root.parent().is_none());
+        assert_eq!(values(&root.children()), vec![2, 3]);
+        assert!(MyTreeNode::ptr_eq(&left.parent().unwrap(), &root));
+        assert!(MyTreeNode::ptr_eq(&leaf.parent().unwrap(), &left));
+
+        let grandparent = leaf.parent().unwrap().parent().unwrap();
+        assert!(MyTreeNode::ptr_eq(&grandparent, &root));
+
+        *leaf.value_mut() = 40;
+        assert_eq!(*root.children()[0].children()[0].value(), 40);
+    }
+
+    #[test]
+    fn dropping_the_root_frees_the_whole_tree() {
+        let dropped = Cell::new(0);
+        let root = MyTreeNode::new(DropFlag(&dropped));
+        let child = MyTreeNode::new(DropFlag(&dropped));
+        child.add_child(MyTreeNode::new(DropFlag(&dropped)));
+        root.add_child(child.clone());
+        root.add_child(MyTreeNode::new(DropFlag(&dropped)));
+
+        // the child handle keeps its subtree alive, the parent link is weak
+        drop(root);
+        assert_eq!(dropped.get(), 2);
+        assert!(child.parent().is_none());
+
+        drop(child);
+        assert_eq!(dropped.get(), 4);
+    }
+
+    #[test]
+    fn add_child_moves_from_the_old_parent() {
+        let first = MyTreeNode::new(1);
+        let second = MyTreeNode::new(2);
+        let child = MyTreeNode::new(3);
+
+        first.add_child(child.clone());
+        second.add_child(child.clone());
+
+        assert!(first.children().is_empty());
+        assert_eq!(values(&second.children()), vec![3]);
+        assert!(MyTreeNode::ptr_eq(&child.parent().unwrap(), &second));
+    }
+
+    #[test]
+    fn detach_makes_a_new_root() {
+        let root = MyTreeNode::new(1);
+        let child = MyTreeNode::new(2);
+        root.add_child(child.clone());
+
+        child.detach();
+        assert!(child.parent().is_none());
+        assert!(root.children().is_empty());
+
+        // detaching a root does nothing
+        child.detach();
+        assert!(child.parent().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "add_child would create a cycle")]
+    fn adding_an_ancestor_as_child_panics() {
+        let root = MyTreeNode::new(1);
+        let child = MyTreeNode::new(2);
+        root.add_child(child.clone());
+
+        child.add_child(root.clone());
+    }
+
+    #[test]
+    #[should_panic(expected = "add_child would create a cycle")]
+    fn adding_a_node_to_itself_panics() {
+        let node = MyTreeNode::new(1);
+        node.add_child(node.clone());
+    }
+}
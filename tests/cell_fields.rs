@@ -0,0 +1,74 @@
+//! # derive(CellFields)
+//! Only built with the derive feature, `cargo test --features derive`.
+#![cfg(feature = "derive")]
+
+use Pointers_Study_With_Core_Concepts::{cell::MyCell, CellFields};
+
+#[derive(CellFields)]
+struct Player {
+    hp: u32,
+    pos: (i32, i32),
+    name: String,
+}
+
+#[derive(CellFields)]
+struct Pair<T> {
+    first: T,
+    count: u8,
+}
+
+fn player() -> MyCell<Player> {
+    MyCell::new(Player {
+        hp: 10,
+        pos: (0, 0),
+        name: String::from("one"),
+    })
+}
+
+#[test]
+fn copy_fields_get_and_set() {
+    let cell = player();
+
+    cell.set_hp(7);
+    cell.set_pos((1, 2));
+    assert_eq!(cell.get_hp(), 7);
+    assert_eq!(cell.get_pos(), (1, 2));
+    assert_eq!(cell.replace_hp(3), 7);
+
+    // the other fields are left alone
+    let player = cell.into_inner();
+    assert_eq!((player.hp, player.pos), (3, (1, 2)));
+    assert_eq!(player.name, "one");
+}
+
+#[test]
+fn non_copy_fields_set_and_replace() {
+    let cell = player();
+
+    assert_eq!(cell.replace_name(String::from("two")), "one");
+    cell.set_name(String::from("three"));
+    assert_eq!(cell.get_hp(), 10);
+    assert_eq!(cell.into_inner().name, "three");
+}
+
+#[test]
+fn generic_fields_get_when_copy() {
+    let cell = MyCell::new(Pair {
+        first: String::from("a"),
+        count: 1,
+    });
+    cell.set_count(2);
+    assert_eq!(cell.replace_first(String::from("b")), "a");
+    assert_eq!(cell.get_count(), 2);
+
+    let copy = MyCell::new(Pair { first: 5, count: 0 });
+    assert_eq!(copy.get_first(), 5);
+}
+
+// Miri can't run the compiler
+#[cfg_attr(miri, ignore)]
+#[test]
+fn get_on_a_non_copy_field_doesnt_compile() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui_derive/*.rs");
+}
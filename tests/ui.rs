@@ -0,0 +1,12 @@
+//! # Compile fail tests
+//! Each file in tests/ui must fail to build, with the error in the .stderr next to it.
+//! After a compiler update changes the wording, regenerate them with
+//! `TRYBUILD=overwrite cargo test --test ui`.
+
+// Miri can't run the compiler
+#[cfg_attr(miri, ignore)]
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,9 @@
+// MyRc's count isn't atomic, so wrapping it in a MyArc
+// must not make it Send
+use Pointers_Study_With_Core_Concepts::{arc::MyArc, rc::MyRc};
+
+fn assert_send<T: Send>() {}
+
+fn main() {
+    assert_send::<MyArc<MyRc<i32>>>();
+}
@@ -0,0 +1,17 @@
+use Pointers_Study_With_Core_Concepts::{cell::MyCell, CellFields};
+
+#[derive(CellFields)]
+struct Player {
+    hp: u32,
+    name: String,
+}
+
+fn main() {
+    let cell = MyCell::new(Player {
+        hp: 1,
+        name: String::new(),
+    });
+    cell.set_hp(2);
+    // String isn't Copy, so there is no get for it
+    let _ = cell.get_name();
+}